@@ -1,20 +1,92 @@
 #version 330
 precision mediump float;
 
+in vec2 o_position;
 in vec2 o_tex;
 in vec4 o_fg_color;
 in vec4 o_bg_color;
+in float o_has_color;
+in float o_glyph_alpha;
+in float o_glyph_progress;
 
 uniform sampler2D glyph_tex;
 uniform bool draw_bg;
+uniform bool draw_box;
+uniform vec4 box_rect;
+uniform float box_radius;
+uniform float border_width;
+uniform vec4 border_color;
+uniform int paint_kind;
+uniform vec4 paint_rect;
+uniform float paint_angle;
+uniform sampler2D paint_ramp;
+// Replaces the paint, for keyframed colors.
+uniform bool use_fill;
+uniform vec4 fill;
+uniform float opacity;
+// Fragments past `reveal_edge` along `reveal_dir` are hidden, for wipes.
+uniform vec2 reveal_dir;
+uniform float reveal_edge;
+uniform int glyph_effect;
+// Tint glyphs start from with the color effect. Color bitmaps aren't
+// tinted, as they aren't by the text color.
+uniform vec4 glyph_color;
 
 out vec4 color;
 
+// Signed distance to a box centered on the origin with rounded corners.
+float rounded_box(vec2 p, vec2 half_size, float radius) {
+    vec2 q = abs(p) - half_size + radius;
+    return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+// Color of a gradient at this fragment, its ramp spanning paint_rect;
+// solid paints keep the vertex color.
+vec4 paint(vec4 solid) {
+    if (use_fill) {
+        return fill;
+    }
+    if (paint_kind == 0) {
+        return solid;
+    }
+    vec2 center = (paint_rect.xy + paint_rect.zw) / 2.0;
+    vec2 size = max(paint_rect.zw - paint_rect.xy, vec2(1.0));
+    float t;
+    if (paint_kind == 1) {
+        // As in CSS, the gradient line is long enough for the corners to
+        // get the end colors.
+        vec2 dir = vec2(sin(paint_angle), -cos(paint_angle));
+        float len = abs(size.x * dir.x) + abs(size.y * dir.y);
+        t = dot(o_position - center, dir) / len + 0.5;
+    } else {
+        t = length((o_position - center) / (size / 2.0)) / sqrt(2.0);
+    }
+    float texel = 1.0 / float(textureSize(paint_ramp, 0).x);
+    return texture(paint_ramp, vec2(mix(texel / 2.0, 1.0 - texel / 2.0, clamp(t, 0.0, 1.0)), 0.5));
+}
+
 void main() {
-    if (draw_bg) {
-        color = o_bg_color;
+    if (draw_box) {
+        vec2 center = (box_rect.xy + box_rect.zw) / 2.0;
+        vec2 half_size = (box_rect.zw - box_rect.xy) / 2.0;
+        float radius = min(box_radius, min(half_size.x, half_size.y));
+        float d = rounded_box(o_position - center, half_size, radius);
+        float inside = clamp(0.5 - (d + border_width), 0.0, 1.0);
+        vec4 fill = paint(o_bg_color);
+        color = border_width > 0.0 ? mix(border_color, fill, inside) : fill;
+        color.a *= clamp(0.5 - d, 0.0, 1.0);
+    } else if (draw_bg) {
+        color = paint(o_bg_color);
     } else {
         color = texture(glyph_tex, o_tex);
-        color.rgb = o_fg_color.rgb;
+        vec4 fg = paint(o_fg_color);
+        if (o_has_color == 0.0) {
+            color.rgb = fg.rgb;
+        }
+        color.a *= fg.a;
+        if (glyph_effect == 5 && o_has_color == 0.0) {
+            color.rgb = mix(glyph_color.rgb, color.rgb, o_glyph_progress);
+        }
     }
-}
\ No newline at end of file
+    color.a *= o_glyph_alpha * opacity * clamp(reveal_edge - dot(o_position, reveal_dir) + 0.5, 0.0, 1.0);
+}
@@ -4,18 +4,85 @@ in vec2 position;
 in vec2 tex;
 in vec4 fg_color;
 in vec4 bg_color;
+in float has_color;
+in float delay;
 
 uniform mat4 projection;
 uniform bool draw_bg;
+// Moves, scales and turns the word around `origin`, for transitions
+// and keyframes.
+uniform vec2 offset;
+uniform float scale;
+// Clockwise, in radians.
+uniform float rotation;
+uniform vec2 origin;
+// Per-glyph animation, 0 for none; the numbers are those of GlyphEffect.
+uniform int glyph_effect;
+// Seconds since the word appeared.
+uniform float glyph_time;
+uniform float glyph_duration;
+// Control points of the easing curve.
+uniform vec4 glyph_easing;
+uniform float glyph_amplitude;
 
+out vec2 o_position;
 out vec2 o_tex;
 out vec4 o_fg_color;
 out vec4 o_bg_color;
+out float o_has_color;
+out float o_glyph_alpha;
+out float o_glyph_progress;
+
+const float PI = 3.14159265;
+
+float bezier(float a, float b, float s) {
+    return 3.0 * a * s * (1.0 - s) * (1.0 - s) + 3.0 * b * s * s * (1.0 - s) + s * s * s;
+}
+
+// As Easing::apply on the CPU.
+float ease(float x) {
+    float low = 0.0;
+    float high = 1.0;
+    for (int i = 0; i < 16; i++) {
+        float mid = (low + high) / 2.0;
+        if (bezier(glyph_easing.x, glyph_easing.z, mid) < x) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    return bezier(glyph_easing.y, glyph_easing.w, (low + high) / 2.0);
+}
 
 void main() {
+    o_position = position;
     o_tex = tex;
     o_fg_color = fg_color;
     o_bg_color = bg_color;
+    o_has_color = has_color;
+
+    float elapsed = glyph_time - delay;
+    float duration = max(glyph_duration, 0.001);
+    float progress = ease(clamp(elapsed / duration, 0.0, 1.0));
+    vec2 shift = vec2(0.0);
+    o_glyph_alpha = 1.0;
+    o_glyph_progress = 1.0;
+    if (glyph_effect == 1) {
+        o_glyph_alpha = step(0.0, elapsed);
+    } else if (glyph_effect == 2) {
+        o_glyph_alpha = progress;
+    } else if (glyph_effect == 3) {
+        // Hops lower each time, landing for good at the end.
+        o_glyph_alpha = step(0.0, elapsed);
+        shift.y = -glyph_amplitude * (1.0 - progress) * abs(cos(progress * 3.0 * PI));
+    } else if (glyph_effect == 4) {
+        shift.y = -glyph_amplitude * sin(2.0 * PI * elapsed / duration);
+    } else if (glyph_effect == 5) {
+        o_glyph_progress = progress;
+    }
 
-    gl_Position = projection * vec4(position, 0.0, 1.0);
+    // y points down, so this turns clockwise on screen.
+    mat2 turn = mat2(cos(rotation), sin(rotation), -sin(rotation), cos(rotation));
+    vec2 moved = origin + turn * ((position + shift - origin) * scale) + offset;
+    gl_Position = projection * vec4(moved, 0.0, 1.0);
 }
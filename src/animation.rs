@@ -0,0 +1,275 @@
+use crate::color::RgbaColor;
+use crate::decoration::Quad;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// Pace of a transition, with the curves of the CSS keywords.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    Linear,
+    #[default]
+    Ease,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Eased progress for a linear progress `t`, both from 0 to 1.
+    pub fn apply(self, t: f32) -> f32 {
+        if self == Easing::Linear {
+            return t;
+        }
+        let [x1, y1, x2, y2] = self.control_points();
+        cubic_bezier(x1, y1, x2, y2, t.clamp(0., 1.))
+    }
+
+    /// Control points of the curve, for shaders to ease with.
+    pub fn control_points(self) -> [f32; 4] {
+        match self {
+            // Points on the diagonal make the curve a straight line.
+            Easing::Linear => [0., 0., 1., 1.],
+            Easing::Ease => [0.25, 0.1, 0.25, 1.],
+            Easing::EaseIn => [0.42, 0., 1., 1.],
+            Easing::EaseOut => [0., 0., 0.58, 1.],
+            Easing::EaseInOut => [0.42, 0., 0.58, 1.],
+        }
+    }
+}
+
+/// A change spread over `duration_ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Transition {
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+impl Transition {
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+
+    /// Eased progress, from 0 to 1, of a transition started at `start`.
+    pub fn progress(&self, start: Instant, now: Instant) -> f32 {
+        if self.duration_ms == 0 {
+            return 1.;
+        }
+        let elapsed = now.saturating_duration_since(start).as_secs_f32();
+        self.easing.apply(elapsed / self.duration().as_secs_f32())
+    }
+}
+
+/// The y of the curve from (0, 0) to (1, 1) with control points
+/// (x1, y1) and (x2, y2) where its x is `x`.
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    let bezier = |a: f32, b: f32, s: f32| {
+        3. * a * s * (1. - s).powi(2) + 3. * b * s * s * (1. - s) + s.powi(3)
+    };
+    // x grows with the curve parameter, so bisection finds it.
+    let (mut low, mut high) = (0f32, 1f32);
+    for _ in 0..24 {
+        let mid = (low + high) / 2.;
+        if bezier(x1, x2, mid) < x {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    bezier(y1, y2, (low + high) / 2.)
+}
+
+/// How a word enters or leaves the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Effect {
+    Fade,
+    /// Moves in or out along `direction`.
+    Slide,
+    /// Grows from, or shrinks to, `scale`.
+    Scale,
+    /// Sharpens from, or fades into, a copy blurred by `blur` pixels.
+    Blur,
+    /// Uncovers, or covers, the word with an edge sweeping along
+    /// `direction`.
+    Wipe,
+}
+
+/// Way a slide or a wipe moves, in screen space.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Side {
+    fn vector(self) -> (f32, f32) {
+        match self {
+            Side::Left => (-1., 0.),
+            Side::Right => (1., 0.),
+            Side::Up => (0., -1.),
+            Side::Down => (0., 1.),
+        }
+    }
+}
+
+const DEFAULT_SLIDE_DISTANCE: f32 = 40.;
+const DEFAULT_BLUR: f32 = 8.;
+
+/// An entrance or exit of a word, written in the input as
+/// `{ "effect": "slide", "direction": "up", "duration_ms": 300 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct WordTransition {
+    pub effect: Effect,
+    #[serde(flatten)]
+    pub timing: Transition,
+    direction: Option<Side>,
+    /// How far a slide goes, in pixels.
+    distance: Option<f32>,
+    /// Size a scaled word starts from or ends at, 1 being its own.
+    scale: Option<f32>,
+    /// Blur radius of a blurred word, in pixels.
+    blur: Option<f32>,
+}
+
+impl WordTransition {
+    pub fn blur_radius(&self) -> Option<f32> {
+        match self.effect {
+            Effect::Blur => Some(self.blur.unwrap_or(DEFAULT_BLUR)),
+            _ => None,
+        }
+    }
+
+    /// The word once `hidden` of the way, from 0 to 1, out of sight;
+    /// `bounds` is where it is drawn.
+    pub fn transform(&self, hidden: f32, entering: bool, bounds: Quad) -> Transform {
+        let direction = self.direction.unwrap_or(Side::Right).vector();
+        let mut transform = Transform::default();
+        match self.effect {
+            Effect::Fade => transform.opacity = 1. - hidden,
+            Effect::Slide => {
+                // Entering words arrive from behind, leaving ones go ahead.
+                let distance = self.distance.unwrap_or(DEFAULT_SLIDE_DISTANCE)
+                    * hidden
+                    * if entering { -1. } else { 1. };
+                transform.offset = (direction.0 * distance, direction.1 * distance);
+            }
+            Effect::Scale => {
+                transform.scale = 1. + (self.scale.unwrap_or(0.) - 1.) * hidden;
+            }
+            Effect::Blur => {
+                transform.opacity = 1. - hidden;
+                transform.blur = hidden;
+            }
+            Effect::Wipe => {
+                // The edge moves along the direction: entering words are
+                // shown behind it, leaving ones ahead of it.
+                let dir = if entering { direction } else { (-direction.0, -direction.1) };
+                let along = [
+                    (bounds.left, bounds.top),
+                    (bounds.right, bounds.top),
+                    (bounds.left, bounds.bottom),
+                    (bounds.right, bounds.bottom),
+                ]
+                .map(|(x, y)| x * dir.0 + y * dir.1);
+                let low = along.iter().copied().fold(f32::INFINITY, f32::min);
+                let high = along.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                transform.reveal = (dir, low + (high - low) * (1. - hidden));
+            }
+        }
+        transform
+    }
+}
+
+/// Where and how visibly the word is drawn in a frame, on top of its
+/// layout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub offset: (f32, f32),
+    /// Around the center of the word.
+    pub scale: f32,
+    /// Clockwise around the center of the word, in radians.
+    pub rotation: f32,
+    pub opacity: f32,
+    /// Share of the blurred copy of the text, against the sharp one.
+    pub blur: f32,
+    /// Only points up to the edge along the direction are shown.
+    pub reveal: ((f32, f32), f32),
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            offset: (0., 0.),
+            scale: 1.,
+            rotation: 0.,
+            opacity: 1.,
+            blur: 0.,
+            reveal: ((0., 0.), 1.),
+        }
+    }
+}
+
+impl Transform {
+    /// Both transforms applied at once.
+    pub fn combine(self, other: Transform) -> Transform {
+        Transform {
+            offset: (self.offset.0 + other.offset.0, self.offset.1 + other.offset.1),
+            scale: self.scale * other.scale,
+            rotation: self.rotation + other.rotation,
+            opacity: self.opacity * other.opacity,
+            blur: self.blur.max(other.blur),
+            reveal: if other.reveal.0 == (0., 0.) { self.reveal } else { other.reveal },
+        }
+    }
+}
+
+/// How each glyph of a word is animated, one after the other in the
+/// reading order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GlyphEffect {
+    /// Glyphs appear at once, as if typed.
+    Typewriter,
+    Fade,
+    /// Glyphs drop from `amplitude` pixels above and bounce into place.
+    Bounce,
+    /// Glyphs keep rising and falling by `amplitude` pixels, a wave
+    /// every `duration_ms`.
+    Wave,
+    /// Glyphs start in `color` and turn into their own.
+    Color,
+}
+
+impl GlyphEffect {
+    /// Number the shaders know the effect by.
+    pub fn id(self) -> i32 {
+        match self {
+            GlyphEffect::Typewriter => 1,
+            GlyphEffect::Fade => 2,
+            GlyphEffect::Bounce => 3,
+            GlyphEffect::Wave => 4,
+            GlyphEffect::Color => 5,
+        }
+    }
+}
+
+pub const DEFAULT_GLYPH_DURATION_MS: u64 = 300;
+pub const DEFAULT_STAGGER_MS: u64 = 50;
+pub const DEFAULT_AMPLITUDE: f32 = 10.;
+
+/// A per-glyph animation, each glyph starting `stagger` after the one
+/// before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphAnimation {
+    pub effect: GlyphEffect,
+    /// Of each glyph.
+    pub timing: Transition,
+    pub stagger: Duration,
+    pub amplitude: f32,
+    pub color: RgbaColor,
+}
@@ -27,6 +27,7 @@ pub trait Texture2d {
 impl Texture2d for SrgbTexture2d {
     fn write(&self, rect: Rect, im: &dyn BitmapImage) {
         let (im_width, im_height) = im.image_dimensions();
+        let is_srgb = im.is_srgb();
 
         let source = glium::texture::RawImage2d {
             data: im
@@ -34,6 +35,9 @@ impl Texture2d for SrgbTexture2d {
                 .iter()
                 .map(|&p| {
                     let (r, g, b, a) = Color(p).as_rgba();
+                    if is_srgb {
+                        return Color::rgba(b, g, r, a).0;
+                    }
 
                     fn conv(v: u8) -> u8 {
                         let f = (v as f32) / 255.;
@@ -78,6 +82,12 @@ pub trait BitmapImage {
 
     fn image_dimensions(&self) -> (usize, usize);
 
+    /// Whether the pixels are already sRGB encoded, with a straight alpha,
+    /// rather than linear values to encode when written.
+    fn is_srgb(&self) -> bool {
+        false
+    }
+
     fn pixels(&self) -> &[u32] {
         let (width, height) = self.image_dimensions();
         unsafe {
@@ -91,6 +101,7 @@ pub struct Image {
     data: Vec<u8>,
     width: usize,
     height: usize,
+    is_srgb: bool,
 }
 
 impl Image {
@@ -98,7 +109,7 @@ impl Image {
         let size = height * width * 4;
         let mut data = vec![0; size];
         data.resize(size, 0);
-        Image { data, width, height }
+        Image { data, width, height, is_srgb: false }
     }
 
     pub fn with_rgba32(width: usize, height: usize, stride: usize, data: &[u8]) -> Image {
@@ -119,6 +130,11 @@ impl Image {
         }
         image
     }
+
+    /// Like `with_rgba32`, for pixels that are already sRGB encoded.
+    pub fn with_srgba32(width: usize, height: usize, stride: usize, data: &[u8]) -> Image {
+        Image { is_srgb: true, ..Image::with_rgba32(width, height, stride, data) }
+    }
 }
 
 impl BitmapImage for Image {
@@ -129,4 +145,8 @@ impl BitmapImage for Image {
     fn image_dimensions(&self) -> (usize, usize) {
         (self.width, self.height)
     }
+
+    fn is_srgb(&self) -> bool {
+        self.is_srgb
+    }
 }
@@ -1,137 +0,0 @@
-use palette::{Srgb, Srgba};
-use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
-use std::result::Result;
-
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
-pub struct RgbColor {
-    pub red: u8,
-    pub green: u8,
-    pub blue: u8,
-}
-
-impl RgbColor {
-    pub fn new(red: u8, green: u8, blue: u8) -> Self {
-        Self { red, green, blue }
-    }
-
-    pub fn to_rgb_string(self) -> String {
-        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
-    }
-
-    pub fn from_rgb_str(s: &str) -> Option<RgbColor> {
-        if s.as_bytes()[0] == b'#' && s.len() == 7 {
-            let mut chars = s.chars().skip(1);
-
-            macro_rules! digit {
-                () => {{
-                    let hi = match chars.next().unwrap().to_digit(16) {
-                        Some(v) => (v as u8) << 4,
-                        None => return None,
-                    };
-                    let lo = match chars.next().unwrap().to_digit(16) {
-                        Some(v) => v as u8,
-                        None => return None,
-                    };
-                    hi | lo
-                }};
-            }
-            Some(Self::new(digit!(), digit!(), digit!()))
-        } else {
-            None
-        }
-    }
-
-    pub fn from_named(name: &str) -> Option<RgbColor> {
-        palette::named::from_str(&name.to_ascii_lowercase()).map(|color| {
-            let color = Srgb::<u8>::from_format(color);
-            Self::new(color.red, color.green, color.blue)
-        })
-    }
-
-    pub fn from_named_or_rgb_string(s: &str) -> Option<Self> {
-        RgbColor::from_rgb_str(s).or_else(|| RgbColor::from_named(s))
-    }
-}
-
-impl Serialize for RgbColor {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let s = self.to_rgb_string();
-        s.serialize(serializer)
-    }
-}
-
-impl<'de> Deserialize<'de> for RgbColor {
-    fn deserialize<D>(deserializer: D) -> Result<RgbColor, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?;
-        RgbColor::from_named_or_rgb_string(&s)
-            .ok_or_else(|| format!("unknown color name: {}", s))
-            .map_err(serde::de::Error::custom)
-    }
-}
-
-#[derive(Copy, Clone, Debug)]
-pub struct Color(pub u32);
-
-impl From<Srgb> for Color {
-    fn from(s: Srgb) -> Color {
-        let b: Srgb<u8> = s.into_format();
-        let b = b.into_components();
-        Color::rgb(b.0, b.1, b.2)
-    }
-}
-
-impl From<Srgba> for Color {
-    fn from(s: Srgba) -> Color {
-        let b: Srgba<u8> = s.into_format();
-        let b = b.into_components();
-        Color::rgba(b.0, b.1, b.2, b.3)
-    }
-}
-
-impl From<Color> for Srgb {
-    fn from(c: Color) -> Srgb {
-        let c = c.as_rgba();
-        let s = Srgb::<u8>::new(c.0, c.1, c.2);
-        s.into_format()
-    }
-}
-
-impl From<Color> for Srgba {
-    fn from(c: Color) -> Srgba {
-        let c = c.as_rgba();
-        let s = Srgba::<u8>::new(c.0, c.1, c.2, c.3);
-        s.into_format()
-    }
-}
-
-impl Color {
-    pub fn rgb(red: u8, green: u8, blue: u8) -> Color {
-        Color::rgba(red, green, blue, 0xff)
-    }
-
-    pub fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Color {
-        let word = (blue as u32) << 24 | (green as u32) << 16 | (red as u32) << 8 | alpha as u32;
-        Color(word.to_be())
-    }
-
-    pub fn as_rgba(self) -> (u8, u8, u8, u8) {
-        let host = u32::from_be(self.0);
-        ((host >> 8) as u8, (host >> 16) as u8, (host >> 24) as u8, (host & 0xff) as u8)
-    }
-
-    pub fn to_tuple_rgba(self) -> (f32, f32, f32, f32) {
-        let c: Srgba = self.into();
-        c.into_format().into_components()
-    }
-}
-
-pub fn to_tuple_rgba(color: RgbColor) -> (f32, f32, f32, f32) {
-    let color = Color::rgba(color.red, color.green, color.blue, 0xff);
-    color.to_tuple_rgba()
-}
@@ -0,0 +1,430 @@
+use palette::{FromColor, LinSrgb, Mix, Oklab, Srgb, Srgba};
+use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::result::Result;
+use std::str::FromStr;
+
+mod parse;
+mod scheme;
+
+pub use parse::{ColorValue, ParseColorError};
+pub use scheme::{ColorScheme, GeneratedColors};
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct RgbaColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl RgbaColor {
+    pub fn new(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Self { red, green, blue, alpha }
+    }
+
+    pub fn to_rgb_string(self) -> String {
+        if self.alpha == 0xff {
+            format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.red, self.green, self.blue, self.alpha)
+        }
+    }
+
+    pub fn from_named(name: &str) -> Option<RgbaColor> {
+        palette::named::from_str(&name.to_ascii_lowercase()).map(|color| {
+            let color = Srgb::<u8>::from_format(color);
+            Self::new(color.red, color.green, color.blue, 0xff)
+        })
+    }
+}
+
+impl FromStr for RgbaColor {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse()? {
+            ColorValue::Rgba(color) => Ok(color),
+            ColorValue::CurrentColor => Err(ParseColorError::CurrentColor),
+        }
+    }
+}
+
+impl Serialize for RgbaColor {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let s = self.to_rgb_string();
+        s.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RgbaColor {
+    fn deserialize<D>(deserializer: D) -> Result<RgbaColor, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A solid color or a gradient, accepted wherever the input takes a
+/// color.
+///
+/// Paints key maps, so angles and offsets are compared and hashed by
+/// their bits: NaN equals itself, and 0 and -0 are different paints.
+#[derive(Debug, Clone)]
+pub enum Paint {
+    Solid(RgbaColor),
+    /// Angle in degrees as in CSS: 0 goes upwards, 90 to the right.
+    Linear {
+        angle: f32,
+        stops: Vec<ColorStop>,
+    },
+    /// Centered on the painted area, reaching its corners.
+    Radial {
+        stops: Vec<ColorStop>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStop {
+    pub color: RgbaColor,
+    /// Position along the gradient, from 0 to 1.
+    pub offset: f32,
+}
+
+impl PartialEq for Paint {
+    fn eq(&self, other: &Self) -> bool {
+        let same_stops = |a: &[ColorStop], b: &[ColorStop]| {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| a.color == b.color && a.offset.to_bits() == b.offset.to_bits())
+        };
+        match (self, other) {
+            (Paint::Solid(a), Paint::Solid(b)) => a == b,
+            (
+                Paint::Linear { angle: a, stops: a_stops },
+                Paint::Linear { angle: b, stops: b_stops },
+            ) => a.to_bits() == b.to_bits() && same_stops(a_stops, b_stops),
+            (Paint::Radial { stops: a }, Paint::Radial { stops: b }) => same_stops(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Paint {}
+
+impl Hash for Paint {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let hash_stops = |stops: &[ColorStop], state: &mut H| {
+            stops.len().hash(state);
+            for stop in stops {
+                stop.color.hash(state);
+                stop.offset.to_bits().hash(state);
+            }
+        };
+        match self {
+            Paint::Solid(color) => color.hash(state),
+            Paint::Linear { angle, stops } => {
+                angle.to_bits().hash(state);
+                hash_stops(stops, state);
+            }
+            Paint::Radial { stops } => hash_stops(stops, state),
+        }
+    }
+}
+
+impl Paint {
+    /// The color standing for the paint where a gradient can't be used:
+    /// the color itself, or the first stop of a gradient.
+    pub fn base_color(&self) -> RgbaColor {
+        match self {
+            Paint::Solid(color) => *color,
+            Paint::Linear { stops, .. } | Paint::Radial { stops } => stops[0].color,
+        }
+    }
+
+    /// Every color the paint goes through.
+    pub fn colors(&self) -> Vec<RgbaColor> {
+        match self {
+            Paint::Solid(color) => vec![*color],
+            Paint::Linear { stops, .. } | Paint::Radial { stops } => {
+                stops.iter().map(|stop| stop.color).collect()
+            }
+        }
+    }
+
+    /// Color at `t`, from 0 to 1, along the gradient; stops are
+    /// interpolated in premultiplied sRGB, as CSS does.
+    pub fn sample(&self, t: f32) -> (f32, f32, f32, f32) {
+        let stops = match self {
+            Paint::Solid(color) => return to_tuple_rgba(*color),
+            Paint::Linear { stops, .. } | Paint::Radial { stops } => stops,
+        };
+        let next = stops.iter().position(|stop| stop.offset > t).unwrap_or(stops.len());
+        if next == 0 {
+            return to_tuple_rgba(stops[0].color);
+        }
+        if next == stops.len() {
+            return to_tuple_rgba(stops[next - 1].color);
+        }
+        let (from, to) = (stops[next - 1], stops[next]);
+        let f = (t - from.offset) / (to.offset - from.offset);
+        let (a, b) = (to_tuple_rgba(from.color), to_tuple_rgba(to.color));
+        // Premultiplied, so that fading to transparent doesn't darken.
+        let alpha = a.3 + (b.3 - a.3) * f;
+        if alpha == 0. {
+            return (0., 0., 0., 0.);
+        }
+        let mix = |x: f32, y: f32| (x * a.3 + (y * b.3 - x * a.3) * f) / alpha;
+        (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2), alpha)
+    }
+
+    /// This paint turned into `to` by `amount`, from 0 to 1, as it is
+    /// drawn halfway through a transition: colors along the gradients
+    /// mixed in OKLab, in the shape of `to`, or of this paint when `to`
+    /// is solid.
+    pub fn mix(&self, to: &Paint, amount: f32) -> Paint {
+        if amount >= 1. {
+            return to.clone();
+        }
+        let color = |(r, g, b, a): (f32, f32, f32, f32)| {
+            let c = |v: f32| (v.clamp(0., 1.) * 255.).round() as u8;
+            RgbaColor::new(c(r), c(g), c(b), c(a))
+        };
+        let stops = || {
+            (0..MIXED_STOPS)
+                .map(|i| {
+                    let t = i as f32 / (MIXED_STOPS - 1) as f32;
+                    ColorStop {
+                        color: color(mix_oklab(self.sample(t), to.sample(t), amount)),
+                        offset: t,
+                    }
+                })
+                .collect()
+        };
+        match (self, to) {
+            (Paint::Solid(_), Paint::Solid(_)) => {
+                Paint::Solid(color(mix_oklab(self.sample(0.), to.sample(0.), amount)))
+            }
+            (_, Paint::Linear { angle, .. }) | (Paint::Linear { angle, .. }, Paint::Solid(_)) => {
+                Paint::Linear { angle: *angle, stops: stops() }
+            }
+            _ => Paint::Radial { stops: stops() },
+        }
+    }
+}
+
+/// Stops sampled along a mix of gradients, enough for OKLab's curve
+/// between two stops to look smooth.
+const MIXED_STOPS: usize = 16;
+
+/// A paint as written in the input, its colors not yet looked up.
+/// Gradients are written as
+/// `{ "type": "linear", "angle": 90, "stops": ["red", "blue"] }`, stops
+/// being colors spread evenly or `{ "color": .., "offset": .. }` objects.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PaintJson {
+    Solid(String),
+    Gradient {
+        #[serde(rename = "type")]
+        kind: GradientKind,
+        angle: Option<f32>,
+        stops: Vec<ColorStopJson>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GradientKind {
+    Linear,
+    Radial,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ColorStopJson {
+    Color(String),
+    Positioned { color: String, offset: f32 },
+}
+
+impl PaintJson {
+    pub fn resolve(&self, palette: &Palette) -> anyhow::Result<Paint> {
+        let (kind, angle, raw_stops) = match self {
+            PaintJson::Solid(color) => return Ok(Paint::Solid(palette.color(color)?)),
+            PaintJson::Gradient { kind, angle, stops } => (kind, angle, stops),
+        };
+        anyhow::ensure!(!raw_stops.is_empty(), "a gradient needs at least one stop");
+        let last = (raw_stops.len() - 1).max(1) as f32;
+        let mut stops = raw_stops
+            .iter()
+            .enumerate()
+            .map(|(i, stop)| match stop {
+                ColorStopJson::Color(color) => {
+                    Ok(ColorStop { color: palette.color(color)?, offset: i as f32 / last })
+                }
+                ColorStopJson::Positioned { color, offset } => {
+                    Ok(ColorStop { color: palette.color(color)?, offset: *offset })
+                }
+            })
+            .collect::<anyhow::Result<Vec<ColorStop>>>()?;
+        stops.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+        Ok(match kind {
+            GradientKind::Linear => Paint::Linear { angle: angle.unwrap_or(180.), stops },
+            GradientKind::Radial => Paint::Radial { stops },
+        })
+    }
+}
+
+/// Colors named in the input, usable wherever a color is; they take
+/// precedence over CSS color names.
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+    colors: HashMap<String, RgbaColor>,
+}
+
+impl Palette {
+    pub fn new(entries: &HashMap<String, String>) -> anyhow::Result<Self> {
+        let mut colors = HashMap::new();
+        for (name, value) in entries {
+            let color =
+                value.parse().map_err(|e| anyhow::anyhow!("palette color {:?}: {}", name, e))?;
+            colors.insert(name.clone(), color);
+        }
+        Ok(Self { colors })
+    }
+
+    pub fn color_value(&self, s: &str) -> Result<ColorValue, ParseColorError> {
+        if let Some(color) = self.colors.get(s.trim()) {
+            return Ok(ColorValue::Rgba(*color));
+        }
+        s.parse().map_err(|e| match e {
+            ParseColorError::UnknownName(name) => ParseColorError::Undefined(name),
+            e => e,
+        })
+    }
+
+    pub fn color(&self, s: &str) -> Result<RgbaColor, ParseColorError> {
+        match self.color_value(s)? {
+            ColorValue::Rgba(color) => Ok(color),
+            ColorValue::CurrentColor => Err(ParseColorError::CurrentColor),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Color(pub u32);
+
+impl From<Srgb> for Color {
+    fn from(s: Srgb) -> Color {
+        let b: Srgb<u8> = s.into_format();
+        let b = b.into_components();
+        Color::rgb(b.0, b.1, b.2)
+    }
+}
+
+impl From<Srgba> for Color {
+    fn from(s: Srgba) -> Color {
+        let b: Srgba<u8> = s.into_format();
+        let b = b.into_components();
+        Color::rgba(b.0, b.1, b.2, b.3)
+    }
+}
+
+impl From<Color> for Srgb {
+    fn from(c: Color) -> Srgb {
+        let c = c.as_rgba();
+        let s = Srgb::<u8>::new(c.0, c.1, c.2);
+        s.into_format()
+    }
+}
+
+impl From<Color> for Srgba {
+    fn from(c: Color) -> Srgba {
+        let c = c.as_rgba();
+        let s = Srgba::<u8>::new(c.0, c.1, c.2, c.3);
+        s.into_format()
+    }
+}
+
+impl Color {
+    pub fn rgb(red: u8, green: u8, blue: u8) -> Color {
+        Color::rgba(red, green, blue, 0xff)
+    }
+
+    pub fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Color {
+        let word = (blue as u32) << 24 | (green as u32) << 16 | (red as u32) << 8 | alpha as u32;
+        Color(word.to_be())
+    }
+
+    pub fn as_rgba(self) -> (u8, u8, u8, u8) {
+        let host = u32::from_be(self.0);
+        ((host >> 8) as u8, (host >> 16) as u8, (host >> 24) as u8, (host & 0xff) as u8)
+    }
+
+    pub fn to_tuple_rgba(self) -> (f32, f32, f32, f32) {
+        let c: Srgba = self.into();
+        c.into_format().into_components()
+    }
+}
+
+pub fn to_tuple_rgba(color: RgbaColor) -> (f32, f32, f32, f32) {
+    let color = Color::rgba(color.red, color.green, color.blue, color.alpha);
+    color.to_tuple_rgba()
+}
+
+/// Mixes two sRGB colors by `amount`, from 0 to 1, in OKLab, where
+/// steps look even and colors in between don't turn muddy.
+pub fn mix_oklab(
+    from: (f32, f32, f32, f32),
+    to: (f32, f32, f32, f32),
+    amount: f32,
+) -> (f32, f32, f32, f32) {
+    if amount >= 1. {
+        return to;
+    }
+    let oklab =
+        |(r, g, b, _): (f32, f32, f32, f32)| Oklab::from_color(Srgb::new(r, g, b).into_linear());
+    let mixed = oklab(from).mix(&oklab(to), amount);
+    let (r, g, b) = Srgb::from_linear(LinSrgb::from_color(mixed)).into_components();
+    let clamp = |c: f32| c.clamp(0., 1.);
+    (clamp(r), clamp(g), clamp(b), from.3 + (to.3 - from.3) * amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash(paint: &Paint) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        paint.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn linear(angle: f32, offset: f32) -> Paint {
+        let color = RgbaColor::new(0xff, 0, 0, 0xff);
+        Paint::Linear { angle, stops: vec![ColorStop { color, offset }] }
+    }
+
+    #[test]
+    fn paints_compare_as_they_hash() {
+        for (a, b) in [
+            (linear(f32::NAN, 0.), linear(f32::NAN, 0.)),
+            (linear(90., f32::NAN), linear(90., f32::NAN)),
+            (linear(0., 0.), linear(-0., 0.)),
+            (linear(90., 0.), linear(90., -0.)),
+            (linear(90., 0.5), linear(90., 0.5)),
+        ] {
+            assert_eq!(a == b, hash(&a) == hash(&b), "{:?} {:?}", a, b);
+        }
+        assert_eq!(linear(f32::NAN, 0.), linear(f32::NAN, 0.));
+        assert_ne!(linear(0., 0.), linear(-0., 0.));
+    }
+}
@@ -0,0 +1,396 @@
+use crate::color::RgbaColor;
+use std::f32::consts::PI;
+use std::str::FromStr;
+use thiserror::*;
+
+/// Why a color couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ParseColorError {
+    #[error("empty color")]
+    Empty,
+    #[error("invalid hex color {0:?}, expected #RGB, #RGBA, #RRGGBB or #RRGGBBAA")]
+    InvalidHex(String),
+    #[error("unknown color function {0}()")]
+    UnknownFunction(String),
+    #[error("{function}() takes {expected} arguments, found {found}")]
+    ArgumentCount { function: String, expected: &'static str, found: usize },
+    #[error("invalid argument {argument:?} to {function}()")]
+    InvalidArgument { function: String, argument: String },
+    #[error("unknown color name {0:?}")]
+    UnknownName(String),
+    #[error("undefined color {0:?}, neither in the palette nor a CSS color name")]
+    Undefined(String),
+    #[error("currentColor can't be used here")]
+    CurrentColor,
+}
+
+/// A color as written in CSS: a color of its own, or `currentColor`,
+/// standing for the color of the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorValue {
+    Rgba(RgbaColor),
+    CurrentColor,
+}
+
+impl ColorValue {
+    pub fn resolve(self, current: RgbaColor) -> RgbaColor {
+        match self {
+            ColorValue::Rgba(color) => color,
+            ColorValue::CurrentColor => current,
+        }
+    }
+
+    /// The color, unless it defers to the text's.
+    pub fn specified(self) -> Option<RgbaColor> {
+        match self {
+            ColorValue::Rgba(color) => Some(color),
+            ColorValue::CurrentColor => None,
+        }
+    }
+}
+
+/// Parses hex colors, the `rgb()`, `hsl()`, `hwb()`, `lab()`, `lch()`,
+/// `oklab()` and `oklch()` functions in both their comma and space
+/// separated forms, named colors, `transparent` and `currentColor`.
+impl FromStr for ColorValue {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseColorError::Empty);
+        }
+        if let Some(hex) = s.strip_prefix('#') {
+            return parse_hex(hex)
+                .map(ColorValue::Rgba)
+                .ok_or_else(|| ParseColorError::InvalidHex(s.to_string()));
+        }
+        if let Some((function, rest)) = s.split_once('(') {
+            let args = rest.strip_suffix(')').ok_or_else(|| ParseColorError::InvalidArgument {
+                function: function.to_string(),
+                argument: rest.to_string(),
+            })?;
+            return parse_function(&function.trim().to_ascii_lowercase(), args)
+                .map(ColorValue::Rgba);
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "currentcolor" => Ok(ColorValue::CurrentColor),
+            "transparent" => Ok(ColorValue::Rgba(RgbaColor::new(0, 0, 0, 0))),
+            name => RgbaColor::from_named(name)
+                .map(ColorValue::Rgba)
+                .ok_or_else(|| ParseColorError::UnknownName(s.to_string())),
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<RgbaColor> {
+    let digits =
+        hex.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect::<Option<Vec<u8>>>()?;
+    let channels: Vec<u8> = match digits.len() {
+        3 | 4 => digits.iter().map(|d| d << 4 | d).collect(),
+        6 | 8 => digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect(),
+        _ => return None,
+    };
+    let alpha = channels.get(3).copied().unwrap_or(0xff);
+    Some(RgbaColor::new(channels[0], channels[1], channels[2], alpha))
+}
+
+/// Splits the arguments of a color function into its three components
+/// and its alpha, written either `a, b, c[, alpha]` or `a b c[ / alpha]`.
+fn split_args(args: &str) -> (Vec<&str>, Option<&str>) {
+    if args.contains(',') {
+        let mut args: Vec<&str> = args.split(',').map(str::trim).collect();
+        let alpha = if args.len() == 4 { args.pop() } else { None };
+        return (args, alpha);
+    }
+    let (components, alpha) = match args.split_once('/') {
+        Some((components, alpha)) => (components, Some(alpha.trim())),
+        None => (args, None),
+    };
+    (components.split_whitespace().collect(), alpha)
+}
+
+const FUNCTIONS: &[&str] = &["rgb", "rgba", "hsl", "hsla", "hwb", "lab", "lch", "oklab", "oklch"];
+
+fn parse_function(function: &str, args: &str) -> Result<RgbaColor, ParseColorError> {
+    if !FUNCTIONS.contains(&function) {
+        return Err(ParseColorError::UnknownFunction(function.to_string()));
+    }
+    let (args, alpha) = split_args(args);
+    if args.len() != 3 {
+        return Err(ParseColorError::ArgumentCount {
+            function: function.to_string(),
+            expected: "3 or 4",
+            found: args.len() + alpha.iter().count(),
+        });
+    }
+    let invalid = |argument: &str| ParseColorError::InvalidArgument {
+        function: function.to_string(),
+        argument: argument.to_string(),
+    };
+    // A number, or a percentage of `full`; `none` is a missing component.
+    let number = |arg: &str, full: f32| -> Result<f32, ParseColorError> {
+        if arg.eq_ignore_ascii_case("none") {
+            return Ok(0.);
+        }
+        let value = match arg.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().map(|p| p / 100. * full),
+            None => arg.parse::<f32>(),
+        };
+        value.ok().filter(|v| v.is_finite()).ok_or_else(|| invalid(arg))
+    };
+    // Hues are in degrees unless another unit is given.
+    let hue = |arg: &str| -> Result<f32, ParseColorError> {
+        let units = [("deg", 1.), ("grad", 0.9), ("rad", 180. / PI), ("turn", 360.)];
+        for (unit, scale) in units {
+            if let Some(value) = arg.strip_suffix(unit) {
+                return value.parse::<f32>().map(|v| v * scale).map_err(|_| invalid(arg));
+            }
+        }
+        number(arg, 0.)
+    };
+    let alpha = match alpha {
+        Some(alpha) => number(alpha, 1.)?.clamp(0., 1.),
+        None => 1.,
+    };
+
+    let rgb = match function {
+        "rgb" | "rgba" => {
+            let channel = |arg| number(arg, 255.).map(|v| v / 255.);
+            [channel(args[0])?, channel(args[1])?, channel(args[2])?]
+        }
+        "hsl" | "hsla" => {
+            hsl_to_srgb(hue(args[0])?, number(args[1], 100.)?, number(args[2], 100.)?)
+        }
+        "hwb" => hwb_to_srgb(hue(args[0])?, number(args[1], 100.)?, number(args[2], 100.)?),
+        "lab" => {
+            lab_to_srgb(number(args[0], 100.)?, number(args[1], 125.)?, number(args[2], 125.)?)
+        }
+        "lch" => {
+            let (a, b) = polar(number(args[1], 150.)?, hue(args[2])?);
+            lab_to_srgb(number(args[0], 100.)?, a, b)
+        }
+        "oklab" => {
+            oklab_to_srgb(number(args[0], 1.)?, number(args[1], 0.4)?, number(args[2], 0.4)?)
+        }
+        "oklch" => {
+            let (a, b) = polar(number(args[1], 0.4)?, hue(args[2])?);
+            oklab_to_srgb(number(args[0], 1.)?, a, b)
+        }
+        _ => unreachable!(),
+    };
+    let to_u8 = |c: f32| (c.clamp(0., 1.) * 255.).round() as u8;
+    Ok(RgbaColor::new(to_u8(rgb[0]), to_u8(rgb[1]), to_u8(rgb[2]), to_u8(alpha)))
+}
+
+fn polar(chroma: f32, hue: f32) -> (f32, f32) {
+    let hue = hue.to_radians();
+    (chroma * hue.cos(), chroma * hue.sin())
+}
+
+/// Saturation and lightness in percent.
+fn hsl_to_srgb(hue: f32, saturation: f32, lightness: f32) -> [f32; 3] {
+    let (s, l) = (saturation.clamp(0., 100.) / 100., lightness.clamp(0., 100.) / 100.);
+    let channel = |n: f32| {
+        let k = (n + hue.rem_euclid(360.) / 30.) % 12.;
+        let a = s * l.min(1. - l);
+        l - a * (k - 3.).min(9. - k).clamp(-1., 1.)
+    };
+    [channel(0.), channel(8.), channel(4.)]
+}
+
+/// Whiteness and blackness in percent.
+fn hwb_to_srgb(hue: f32, whiteness: f32, blackness: f32) -> [f32; 3] {
+    let (w, b) = (whiteness.clamp(0., 100.) / 100., blackness.clamp(0., 100.) / 100.);
+    if w + b >= 1. {
+        let gray = w / (w + b);
+        return [gray; 3];
+    }
+    hsl_to_srgb(hue, 100., 50.).map(|c| c * (1. - w - b) + w)
+}
+
+/// CIE Lab, relative to the D50 white as in CSS.
+fn lab_to_srgb(lightness: f32, a: f32, b: f32) -> [f32; 3] {
+    const EPSILON: f32 = 216. / 24389.;
+    const KAPPA: f32 = 24389. / 27.;
+    const WHITE: [f32; 3] = [0.3457 / 0.3585, 1., (1. - 0.3457 - 0.3585) / 0.3585];
+
+    let fy = (lightness + 16.) / 116.;
+    let fx = a / 500. + fy;
+    let fz = fy - b / 200.;
+    let inverse = |f: f32| if f.powi(3) > EPSILON { f.powi(3) } else { (116. * f - 16.) / KAPPA };
+    let y = if lightness > KAPPA * EPSILON { fy.powi(3) } else { lightness / KAPPA };
+    let xyz = [inverse(fx) * WHITE[0], y * WHITE[1], inverse(fz) * WHITE[2]];
+
+    // Bradford-adapted XYZ D50 to linear sRGB, as given by CSS Color 4.
+    let linear = multiply(
+        [
+            [3.134_136, -1.617_386, -0.490_662],
+            [-0.978_795, 1.916_254, 0.033_443],
+            [0.071_955, -0.228_977, 1.405_386],
+        ],
+        xyz,
+    );
+    linear.map(gamma)
+}
+
+fn oklab_to_srgb(lightness: f32, a: f32, b: f32) -> [f32; 3] {
+    let lms = multiply(
+        [
+            [1., 0.396_337_8, 0.215_803_76],
+            [1., -0.105_561_35, -0.063_854_17],
+            [1., -0.089_484_18, -1.291_485_5],
+        ],
+        [lightness, a, b],
+    )
+    .map(|c| c.powi(3));
+    let linear = multiply(
+        [
+            [4.076_741_7, -3.307_711_6, 0.230_969_94],
+            [-1.268_438, 2.609_757_4, -0.341_319_38],
+            [-0.004_196_086_3, -0.703_418_6, 1.707_614_7],
+        ],
+        lms,
+    );
+    linear.map(gamma)
+}
+
+fn multiply(matrix: [[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+/// Linear light to the sRGB transfer function.
+fn gamma(c: f32) -> f32 {
+    if c.abs() > 0.003_130_8 {
+        c.signum() * (1.055 * c.abs().powf(1. / 2.4) - 0.055)
+    } else {
+        12.92 * c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<ColorValue, ParseColorError> {
+        s.parse()
+    }
+
+    fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Result<ColorValue, ParseColorError> {
+        Ok(ColorValue::Rgba(RgbaColor::new(red, green, blue, alpha)))
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(parse("#f80"), rgba(0xff, 0x88, 0x00, 0xff));
+        assert_eq!(parse("#f808"), rgba(0xff, 0x88, 0x00, 0x88));
+        assert_eq!(parse("#FF8800"), rgba(0xff, 0x88, 0x00, 0xff));
+        assert_eq!(parse("#ff880080"), rgba(0xff, 0x88, 0x00, 0x80));
+        assert_eq!(parse("  #000  "), rgba(0, 0, 0, 0xff));
+    }
+
+    #[test]
+    fn invalid_hex() {
+        for s in ["#", "#ff", "#fffff", "#fffffffff", "#ggg", "#ff 00 00"] {
+            assert_eq!(parse(s), Err(ParseColorError::InvalidHex(s.to_string())), "{}", s);
+        }
+    }
+
+    #[test]
+    fn rgb() {
+        assert_eq!(parse("rgb(255, 136, 0)"), rgba(255, 136, 0, 255));
+        assert_eq!(parse("rgba(255, 136, 0, 0.5)"), rgba(255, 136, 0, 128));
+        assert_eq!(parse("rgb(255 136 0)"), rgba(255, 136, 0, 255));
+        assert_eq!(parse("rgb(100% 0% 50% / 25%)"), rgba(255, 0, 128, 64));
+        assert_eq!(parse("RGB(none 0 0)"), rgba(0, 0, 0, 255));
+    }
+
+    #[test]
+    fn hsl_and_hwb() {
+        assert_eq!(parse("hsl(0, 100%, 50%)"), rgba(255, 0, 0, 255));
+        assert_eq!(parse("hsl(120deg 100% 25%)"), rgba(0, 128, 0, 255));
+        assert_eq!(parse("hsla(0.5turn, 100%, 50%, 0)"), rgba(0, 255, 255, 0));
+        assert_eq!(parse("hsl(-120, 100%, 50%)"), rgba(0, 0, 255, 255));
+        assert_eq!(parse("hsl(0, 0%, 100%)"), rgba(255, 255, 255, 255));
+        assert_eq!(parse("hwb(0 0% 0%)"), rgba(255, 0, 0, 255));
+        assert_eq!(parse("hwb(0 60% 60%)"), rgba(128, 128, 128, 255));
+    }
+
+    #[test]
+    fn lab_and_oklab() {
+        assert_eq!(parse("lab(100 0 0)"), rgba(255, 255, 255, 255));
+        assert_eq!(parse("lab(0 0 0)"), rgba(0, 0, 0, 255));
+        assert_eq!(parse("lch(54.29 106.84 40.85)"), rgba(255, 0, 0, 255));
+        assert_eq!(parse("oklab(1 0 0)"), rgba(255, 255, 255, 255));
+        assert_eq!(parse("oklch(62.8% 0.2577 29.23)"), rgba(255, 0, 0, 255));
+    }
+
+    #[test]
+    fn out_of_range_components_are_clamped() {
+        assert_eq!(parse("rgb(300, -20, 128)"), rgba(255, 0, 128, 255));
+        assert_eq!(parse("rgb(0 0 0 / 2)"), rgba(0, 0, 0, 255));
+        assert_eq!(parse("rgb(0 0 0 / -50%)"), rgba(0, 0, 0, 0));
+        assert_eq!(parse("hsl(0, 150%, 150%)"), rgba(255, 255, 255, 255));
+        assert_eq!(parse("oklab(2 0 0)"), rgba(255, 255, 255, 255));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(parse("rebeccapurple"), rgba(0x66, 0x33, 0x99, 0xff));
+        assert_eq!(parse("White"), rgba(0xff, 0xff, 0xff, 0xff));
+        assert_eq!(parse("transparent"), rgba(0, 0, 0, 0));
+        assert_eq!(parse("currentColor"), Ok(ColorValue::CurrentColor));
+        assert_eq!(parse("blurple"), Err(ParseColorError::UnknownName("blurple".to_string())));
+    }
+
+    #[test]
+    fn malformed_functions() {
+        assert_eq!(parse(""), Err(ParseColorError::Empty));
+        assert_eq!(parse("   "), Err(ParseColorError::Empty));
+        assert_eq!(parse("cmyk(0, 0, 0, 0)"), Err(ParseColorError::UnknownFunction("cmyk".into())));
+        assert_eq!(
+            parse("rgb(1, 2)"),
+            Err(ParseColorError::ArgumentCount {
+                function: "rgb".into(),
+                expected: "3 or 4",
+                found: 2
+            })
+        );
+        assert_eq!(
+            parse("rgb(1 2 3 4)"),
+            Err(ParseColorError::ArgumentCount {
+                function: "rgb".into(),
+                expected: "3 or 4",
+                found: 4
+            })
+        );
+        assert_eq!(
+            parse("rgb(1, 2, 3"),
+            Err(ParseColorError::InvalidArgument {
+                function: "rgb".into(),
+                argument: "1, 2, 3".into()
+            })
+        );
+        assert_eq!(
+            parse("rgb(red, 0, 0)"),
+            Err(ParseColorError::InvalidArgument {
+                function: "rgb".into(),
+                argument: "red".into()
+            })
+        );
+        assert_eq!(
+            parse("hsl(1xyz, 0%, 0%)"),
+            Err(ParseColorError::InvalidArgument {
+                function: "hsl".into(),
+                argument: "1xyz".into()
+            })
+        );
+        assert_eq!(
+            parse("rgb(inf, 0, 0)"),
+            Err(ParseColorError::InvalidArgument {
+                function: "rgb".into(),
+                argument: "inf".into()
+            })
+        );
+    }
+}
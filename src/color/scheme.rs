@@ -0,0 +1,116 @@
+use crate::color::RgbaColor;
+use crate::contrast::{self, wcag_ratio};
+use palette::{FromColor, Hsl, Srgb};
+use serde::Deserialize;
+
+/// How the hues of a generated word relate to each other.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemeMode {
+    /// Unrelated canvas and text hues.
+    #[default]
+    Random,
+    /// Text opposite the canvas on the color wheel.
+    Complementary,
+    /// Box and text 30° on either side of the canvas.
+    Analogous,
+    /// Canvas, box and text a third of the wheel apart.
+    Triadic,
+}
+
+/// Colors derived for the words that don't set them, the same from one
+/// run to the next for a given seed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ColorScheme {
+    #[serde(default)]
+    pub mode: SchemeMode,
+    #[serde(default)]
+    pub seed: u64,
+    /// WCAG 2.x ratio the text reaches against what it is drawn on.
+    pub min_contrast: Option<f32>,
+}
+
+/// Colors generated for one word. The text color depends on the
+/// background it ends up on, which the word may set itself.
+pub struct GeneratedColors {
+    pub canvas: RgbaColor,
+    pub bg: Option<RgbaColor>,
+    fg_hue: f32,
+    fg_saturation: f32,
+    min_contrast: f32,
+}
+
+impl ColorScheme {
+    /// Colors for the word at `index`, which don't depend on the words
+    /// before it.
+    pub fn generate(&self, index: usize) -> GeneratedColors {
+        let mut rng = SplitMix64(self.seed ^ (index as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let hue = rng.next_f32() * 360.;
+        let saturation = 0.4 + rng.next_f32() * 0.4;
+        let lightness = 0.15 + rng.next_f32() * 0.7;
+        let (bg_hue, fg_hue) = match self.mode {
+            SchemeMode::Random => (None, rng.next_f32() * 360.),
+            SchemeMode::Complementary => (None, hue + 180.),
+            SchemeMode::Analogous => (Some(hue + 30.), hue - 30.),
+            SchemeMode::Triadic => (Some(hue + 120.), hue + 240.),
+        };
+        // The box stands out from the canvas by lightness as well as hue.
+        let bg_lightness = if lightness < 0.5 { lightness + 0.3 } else { lightness - 0.3 };
+        GeneratedColors {
+            canvas: hsl(hue, saturation, lightness),
+            bg: bg_hue.map(|bg_hue| hsl(bg_hue, saturation, bg_lightness)),
+            fg_hue,
+            fg_saturation: 0.5 + rng.next_f32() * 0.5,
+            min_contrast: self.min_contrast.unwrap_or(contrast::DEFAULT_MIN_RATIO),
+        }
+    }
+}
+
+impl GeneratedColors {
+    /// A text color reaching the minimum contrast against every color of
+    /// `backgrounds`: the generated hue made lighter or darker as needed,
+    /// or black or white when no shade of it is enough.
+    pub fn fg_on(&self, backgrounds: &[RgbaColor]) -> RgbaColor {
+        let worst = |color: RgbaColor| {
+            backgrounds.iter().map(|&bg| wcag_ratio(color, bg)).fold(f32::INFINITY, f32::min)
+        };
+        let shade = |lightness: f32| hsl(self.fg_hue, self.fg_saturation, lightness);
+        let target = if worst(shade(1.)) >= worst(shade(0.)) { 1. } else { 0. };
+        for step in 0..=10 {
+            let color = shade(0.5 + (target - 0.5) * step as f32 / 10.);
+            if worst(color) >= self.min_contrast {
+                return color;
+            }
+        }
+        let (black, white) =
+            (RgbaColor::new(0, 0, 0, 0xff), RgbaColor::new(0xff, 0xff, 0xff, 0xff));
+        if worst(black) >= worst(white) {
+            black
+        } else {
+            white
+        }
+    }
+}
+
+fn hsl(hue: f32, saturation: f32, lightness: f32) -> RgbaColor {
+    let rgb: Srgb<u8> = Srgb::from_color(Hsl::new(hue, saturation, lightness)).into_format();
+    RgbaColor::new(rgb.red, rgb.green, rgb.blue, 0xff)
+}
+
+/// A small seeded generator, enough to pick colors.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
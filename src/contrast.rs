@@ -0,0 +1,240 @@
+use crate::color::{Paint, RgbaColor};
+use crate::input::Word;
+
+/// WCAG 2.x AA minimum for body text.
+pub const DEFAULT_MIN_RATIO: f32 = 4.5;
+/// APCA minimum for body text.
+pub const DEFAULT_MIN_LC: f32 = 60.;
+
+/// Minimum contrast a word should reach.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// WCAG 2.x contrast ratio, from 1 to 21.
+    pub ratio: f32,
+    /// APCA lightness contrast, compared regardless of polarity.
+    pub lc: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self { ratio: DEFAULT_MIN_RATIO, lc: DEFAULT_MIN_LC }
+    }
+}
+
+/// Contrast between the text of a word and what it is drawn on.
+#[derive(Debug, Clone, Copy)]
+pub struct Contrast {
+    pub ratio: f32,
+    /// APCA Lc, negative for light text on a dark background.
+    pub lc: f32,
+}
+
+impl Contrast {
+    pub fn between(fg: RgbaColor, bg: RgbaColor) -> Self {
+        let fg = blend(fg, bg);
+        Self { ratio: wcag_ratio(fg, bg), lc: apca_lc(fg, bg) }
+    }
+
+    /// Contrast of a word against its `bg_color`, or the canvas when it
+    /// has none. With gradients, the worst pair of stops is kept for each
+    /// measure, which may not be the same pair for both.
+    pub fn of_word(word: &Word) -> Self {
+        Self::of_paints(&word.style.fg_color, word.style.bg_color.as_ref(), &word.canvas_color)
+    }
+
+    fn of_paints(fg: &Paint, bg: Option<&Paint>, canvas: &Paint) -> Self {
+        // The canvas is drawn over black.
+        let canvas = canvas.colors().into_iter().map(|c| blend(c, RgbaColor::new(0, 0, 0, 0xff)));
+        let backgrounds: Vec<RgbaColor> = match bg {
+            Some(bg) => {
+                canvas.flat_map(|c| bg.colors().into_iter().map(move |bg| blend(bg, c))).collect()
+            }
+            None => canvas.collect(),
+        };
+        let mut worst = Contrast { ratio: f32::INFINITY, lc: f32::INFINITY };
+        for fg in fg.colors() {
+            for &bg in &backgrounds {
+                let contrast = Self::between(fg, bg);
+                worst.ratio = worst.ratio.min(contrast.ratio);
+                if contrast.lc.abs() < worst.lc.abs() {
+                    worst.lc = contrast.lc;
+                }
+            }
+        }
+        worst
+    }
+
+    pub fn passes(&self, thresholds: &Thresholds) -> bool {
+        self.ratio >= thresholds.ratio && self.lc.abs() >= thresholds.lc
+    }
+}
+
+/// `top` composited over the opaque `bottom`.
+fn blend(top: RgbaColor, bottom: RgbaColor) -> RgbaColor {
+    let alpha = top.alpha as f32 / 255.;
+    let mix = |t: u8, b: u8| (t as f32 * alpha + b as f32 * (1. - alpha)).round() as u8;
+    RgbaColor::new(
+        mix(top.red, bottom.red),
+        mix(top.green, bottom.green),
+        mix(top.blue, bottom.blue),
+        0xff,
+    )
+}
+
+fn channels(color: RgbaColor) -> [f32; 3] {
+    [color.red, color.green, color.blue].map(|c| c as f32 / 255.)
+}
+
+/// WCAG 2.x relative luminance.
+fn relative_luminance(color: RgbaColor) -> f32 {
+    let [r, g, b] =
+        channels(color).map(
+            |c| {
+                if c <= 0.04045 {
+                    c / 12.92
+                } else {
+                    ((c + 0.055) / 1.055).powf(2.4)
+                }
+            },
+        );
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+pub fn wcag_ratio(a: RgbaColor, b: RgbaColor) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// APCA lightness contrast of `text` on `bg`, following the 0.0.98G
+/// constants.
+pub fn apca_lc(text: RgbaColor, bg: RgbaColor) -> f32 {
+    const BLACK_THRESHOLD: f32 = 0.022;
+    const BLACK_CLAMP: f32 = 1.414;
+    const SCALE: f32 = 1.14;
+    const OFFSET: f32 = 0.027;
+    const LOW_CLIP: f32 = 0.1;
+
+    let luminance = |color| {
+        let [r, g, b] = channels(color).map(|c| c.powf(2.4));
+        let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+        if y < BLACK_THRESHOLD {
+            y + (BLACK_THRESHOLD - y).powf(BLACK_CLAMP)
+        } else {
+            y
+        }
+    };
+    let (text, bg) = (luminance(text), luminance(bg));
+    if (bg - text).abs() < 0.0005 {
+        return 0.;
+    }
+    let lc = if bg > text {
+        let sapc = (bg.powf(0.56) - text.powf(0.57)) * SCALE;
+        if sapc < LOW_CLIP {
+            0.
+        } else {
+            sapc - OFFSET
+        }
+    } else {
+        let sapc = (bg.powf(0.65) - text.powf(0.62)) * SCALE;
+        if sapc > -LOW_CLIP {
+            0.
+        } else {
+            sapc + OFFSET
+        }
+    };
+    lc * 100.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorStop;
+
+    const BLACK: RgbaColor = RgbaColor { red: 0, green: 0, blue: 0, alpha: 0xff };
+    const WHITE: RgbaColor = RgbaColor { red: 0xff, green: 0xff, blue: 0xff, alpha: 0xff };
+
+    fn gray(level: u8) -> RgbaColor {
+        RgbaColor::new(level, level, level, 0xff)
+    }
+
+    fn gradient(colors: &[RgbaColor]) -> Paint {
+        let last = (colors.len() - 1) as f32;
+        let stops = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| ColorStop { color, offset: i as f32 / last })
+            .collect();
+        Paint::Linear { angle: 90., stops }
+    }
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() <= tolerance, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn wcag_reference_values() {
+        assert_close(wcag_ratio(BLACK, WHITE), 21., 0.01);
+        assert_close(wcag_ratio(WHITE, BLACK), 21., 0.01);
+        assert_close(wcag_ratio(gray(0x77), WHITE), 4.48, 0.01);
+        assert_close(wcag_ratio(gray(0x76), WHITE), 4.54, 0.01);
+        assert_close(wcag_ratio(WHITE, WHITE), 1., 0.);
+    }
+
+    #[test]
+    fn apca_reference_values() {
+        assert_close(apca_lc(BLACK, WHITE), 106.04, 0.1);
+        assert_close(apca_lc(WHITE, BLACK), -107.88, 0.1);
+        assert_close(apca_lc(gray(0x88), WHITE), 63.06, 0.1);
+        assert_close(apca_lc(WHITE, gray(0x88)), -68.54, 0.1);
+        assert_eq!(apca_lc(WHITE, WHITE), 0.);
+    }
+
+    #[test]
+    fn translucent_text_is_blended_first() {
+        let half_black = RgbaColor::new(0, 0, 0, 0x80);
+        let contrast = Contrast::between(half_black, WHITE);
+        assert_close(contrast.ratio, wcag_ratio(gray(0x7f), WHITE), 0.01);
+    }
+
+    #[test]
+    fn worst_stop_of_gradients() {
+        let fg = gradient(&[WHITE, gray(0xcc)]);
+        let canvas = gradient(&[BLACK, gray(0x44)]);
+        let contrast = Contrast::of_paints(&fg, None, &canvas);
+        assert_close(contrast.ratio, wcag_ratio(gray(0xcc), gray(0x44)), 0.01);
+        assert_close(contrast.lc, apca_lc(gray(0xcc), gray(0x44)), 0.01);
+    }
+
+    #[test]
+    fn worst_ratio_and_lc_can_come_from_different_stops() {
+        // The lowest ratio is light gray on white, the lowest Lc dark
+        // gray on black.
+        let canvas = gradient(&[WHITE, BLACK]);
+        let fg = gradient(&[gray(0x60), gray(0xb0)]);
+        let contrast = Contrast::of_paints(&fg, None, &canvas);
+        assert_close(contrast.ratio, wcag_ratio(gray(0xb0), WHITE), 0.);
+        assert_close(contrast.lc, apca_lc(gray(0x60), BLACK), 0.);
+    }
+
+    #[test]
+    fn translucent_bg_color_over_the_canvas() {
+        let bg = Paint::Solid(RgbaColor::new(0xff, 0xff, 0xff, 0x80));
+        let contrast = Contrast::of_paints(&Paint::Solid(BLACK), Some(&bg), &Paint::Solid(BLACK));
+        assert_close(contrast.ratio, wcag_ratio(BLACK, gray(0x80)), 0.01);
+
+        // Every stop of the box is laid over every stop of the canvas.
+        let bg = gradient(&[RgbaColor::new(0xff, 0xff, 0xff, 0x80), WHITE]);
+        let canvas = gradient(&[WHITE, BLACK]);
+        let contrast = Contrast::of_paints(&Paint::Solid(BLACK), Some(&bg), &canvas);
+        assert_close(contrast.ratio, wcag_ratio(BLACK, gray(0x80)), 0.01);
+    }
+
+    #[test]
+    fn transparent_canvas_is_black() {
+        let canvas = Paint::Solid(RgbaColor::new(0xff, 0xff, 0xff, 0));
+        let contrast = Contrast::of_paints(&Paint::Solid(WHITE), None, &canvas);
+        assert_close(contrast.ratio, 21., 0.01);
+        assert!(!contrast.passes(&Thresholds { ratio: 21.5, lc: 60. }));
+        assert!(contrast.passes(&Thresholds::default()));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::input::DecorationStyle;
+
+/// Axis-aligned rectangle in canvas coordinates, y pointing down.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Quad {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Quad {
+    fn centered(left: f32, right: f32, center_y: f32, thickness: f32) -> Self {
+        Self { left, top: center_y - thickness / 2., right, bottom: center_y + thickness / 2. }
+    }
+}
+
+/// Splits a decoration line from `left` to `right`, centered on
+/// `center_y`, into the quads that draw it in the given style.
+pub fn decoration_quads(
+    style: DecorationStyle,
+    left: f32,
+    right: f32,
+    center_y: f32,
+    thickness: f32,
+) -> Vec<Quad> {
+    let thickness = thickness.max(1.);
+    match style {
+        DecorationStyle::Single => vec![Quad::centered(left, right, center_y, thickness)],
+        DecorationStyle::Double => {
+            // Two lines with a gap of one thickness, keeping the pair
+            // centered where a single line would be.
+            let offset = thickness;
+            vec![
+                Quad::centered(left, right, center_y - offset, thickness),
+                Quad::centered(left, right, center_y + offset, thickness),
+            ]
+        }
+        DecorationStyle::Dotted => dashes(left, right, center_y, thickness, thickness, thickness),
+        DecorationStyle::Dashed => {
+            dashes(left, right, center_y, thickness, 3. * thickness, 2. * thickness)
+        }
+        DecorationStyle::Wavy => wave(left, right, center_y, thickness),
+    }
+}
+
+fn dashes(left: f32, right: f32, center_y: f32, thickness: f32, dash: f32, gap: f32) -> Vec<Quad> {
+    let mut quads = Vec::new();
+    let mut x = left;
+    while x < right {
+        quads.push(Quad::centered(x, (x + dash).min(right), center_y, thickness));
+        x += dash + gap;
+    }
+    quads
+}
+
+/// Approximates a sine wave with thin vertical slices, each tall enough
+/// to overlap its neighbours so the stroke stays continuous.
+fn wave(left: f32, right: f32, center_y: f32, thickness: f32) -> Vec<Quad> {
+    let amplitude = thickness * 1.5;
+    let wavelength = thickness * 8.;
+    let step = (thickness / 2.).max(0.5);
+    let y_at =
+        |x: f32| center_y + amplitude * (std::f32::consts::TAU * (x - left) / wavelength).sin();
+
+    let mut quads = Vec::new();
+    let mut x = left;
+    while x < right {
+        let next = (x + step).min(right);
+        let (y0, y1) = (y_at(x), y_at(next));
+        quads.push(Quad {
+            left: x,
+            top: y0.min(y1) - thickness / 2.,
+            right: next,
+            bottom: y0.max(y1) + thickness / 2.,
+        });
+        x = next;
+    }
+    quads
+}
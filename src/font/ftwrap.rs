@@ -1,6 +1,9 @@
-use crate::font::loader::FontDataHandle;
+use crate::font::loader::{FontDataHandle, Synthesis};
+use crate::font::rasterizer::FontMetrics;
+use crate::utils::PixelLength;
 use anyhow::{anyhow, Context, Result};
 pub use freetype::freetype::*;
+use freetype::tt_os2::TT_OS2;
 use libc::{self, c_long, c_void, size_t};
 use std::ptr;
 use std::rc::Rc;
@@ -22,7 +25,73 @@ fn render_mode_to_load_target(render_mode: FT_Render_Mode) -> u32 {
     (render_mode as u32) & 15 << 16
 }
 
-pub fn compute_load_flags() -> (i32, FT_Render_Mode) {
+#[allow(non_camel_case_types)]
+type FT_Stroker = *mut c_void;
+#[allow(non_camel_case_types)]
+type FT_Glyph = *mut FT_GlyphRec;
+
+/// Header shared by every `FT_Glyph`, from `ftglyph.h`.
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct FT_GlyphRec {
+    library: FT_Library,
+    clazz: *const c_void,
+    format: FT_Glyph_Format,
+    advance: FT_Vector,
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct FT_BitmapGlyphRec {
+    root: FT_GlyphRec,
+    left: FT_Int,
+    top: FT_Int,
+    bitmap: FT_Bitmap,
+}
+
+const FT_STROKER_LINECAP_ROUND: u32 = 1;
+const FT_STROKER_LINEJOIN_ROUND: u32 = 0;
+
+// The glyph and stroker APIs aren't covered by the freetype crate.
+extern "C" {
+    fn FT_Stroker_New(library: FT_Library, astroker: *mut FT_Stroker) -> FT_Error;
+    fn FT_Stroker_Set(
+        stroker: FT_Stroker,
+        radius: FT_Fixed,
+        line_cap: u32,
+        line_join: u32,
+        miter_limit: FT_Fixed,
+    );
+    fn FT_Stroker_Done(stroker: FT_Stroker);
+    fn FT_Get_Glyph(slot: FT_GlyphSlot, aglyph: *mut FT_Glyph) -> FT_Error;
+    fn FT_Glyph_StrokeBorder(
+        pglyph: *mut FT_Glyph,
+        stroker: FT_Stroker,
+        inside: FT_Bool,
+        destroy: FT_Bool,
+    ) -> FT_Error;
+    fn FT_Glyph_To_Bitmap(
+        the_glyph: *mut FT_Glyph,
+        render_mode: FT_Render_Mode,
+        origin: *const FT_Vector,
+        destroy: FT_Bool,
+    ) -> FT_Error;
+    fn FT_Done_Glyph(glyph: FT_Glyph);
+}
+
+/// Horizontal shear applied to outlines for synthetic oblique, roughly
+/// 12 degrees, matching `FT_GlyphSlot_Oblique`.
+const OBLIQUE_MATRIX: FT_Matrix = FT_Matrix { xx: 0x10000, xy: 0x0366A, yx: 0, yy: 0x10000 };
+
+pub fn compute_load_flags(has_color: bool) -> (i32, FT_Render_Mode) {
+    // FreeType only composites COLR layers and keeps BGRA bitmaps when
+    // rendering in normal mode, so color faces give up subpixel AA.
+    if has_color {
+        let render = FT_Render_Mode::FT_RENDER_MODE_NORMAL;
+        let flags = render_mode_to_load_target(render) | FT_LOAD_COLOR;
+        return (flags as i32, render);
+    }
+
     let render = FT_Render_Mode::FT_RENDER_MODE_LCD;
 
     let flags = render_mode_to_load_target(render);
@@ -64,9 +133,101 @@ impl Drop for Face {
 }
 
 impl Face {
-    pub fn set_font_size(&mut self, point_size: f64, dpi: u32) -> Result<()> {
-        let size = (point_size * 64.0) as FT_F26Dot6;
-        self.set_char_size(size, 0, dpi, 0)
+    /// Sets the face size and returns the factor by which glyphs have to
+    /// be scaled to reach it. This is 1 for scalable faces; bitmap-only
+    /// faces (CBDT/sbix emoji) use the closest strike instead.
+    pub fn set_font_size(&mut self, point_size: f64, dpi: u32) -> Result<f64> {
+        if self.is_scalable() {
+            let size = (point_size * 64.0) as FT_F26Dot6;
+            self.set_char_size(size, 0, dpi, 0)?;
+            return Ok(1.0);
+        }
+        let pixel_size = point_size * dpi as f64 / 72.0;
+        let strike_size = self.select_strike(pixel_size)?;
+        Ok(pixel_size / strike_size)
+    }
+
+    /// Metrics at the current size, read from the size metrics, the
+    /// `OS/2` table and the `post` underline fields. Values missing from
+    /// the font are derived from the em size.
+    pub fn metrics(&self) -> FontMetrics {
+        unsafe {
+            let face = &*self.face;
+            let size = &(*face.size).metrics;
+            let em = size.y_ppem as f64;
+            let scalable = self.is_scalable();
+            let to_px = |units: FT_Short| -> Option<PixelLength> {
+                if scalable && units != 0 {
+                    let pos = FT_MulFix(units as FT_Long, size.y_scale);
+                    Some(PixelLength::new(pos as f64 / 64.0))
+                } else {
+                    None
+                }
+            };
+
+            let ascender = PixelLength::new(size.ascender as f64 / 64.0);
+            let descender = PixelLength::new(size.descender as f64 / 64.0);
+            let line_height = PixelLength::new(size.height as f64 / 64.0);
+
+            let os2 = FT_Get_Sfnt_Table(self.face, FT_Sfnt_Tag::FT_SFNT_OS2) as *const TT_OS2;
+            let os2 = os2.as_ref();
+            let os2_v2 = os2.filter(|os2| os2.version >= 2 && os2.version != 0xffff);
+
+            let x_height = os2_v2
+                .and_then(|os2| to_px(os2.sxHeight))
+                .unwrap_or_else(|| PixelLength::new(em * 0.5));
+            let cap_height = os2_v2
+                .and_then(|os2| to_px(os2.sCapHeight))
+                .unwrap_or_else(|| PixelLength::new(em * 0.7));
+            let underline_thickness = to_px(face.underline_thickness)
+                .unwrap_or_else(|| PixelLength::new(em / 14.0))
+                .max(PixelLength::new(1.0));
+            let underline_position =
+                to_px(face.underline_position).unwrap_or_else(|| PixelLength::new(-em / 10.0));
+            let strikeout_thickness = os2
+                .and_then(|os2| to_px(os2.yStrikeoutSize))
+                .unwrap_or(underline_thickness)
+                .max(PixelLength::new(1.0));
+            let strikeout_position =
+                os2.and_then(|os2| to_px(os2.yStrikeoutPosition)).unwrap_or(x_height / 2.0);
+
+            FontMetrics {
+                ascender,
+                descender,
+                line_gap: line_height - (ascender - descender),
+                line_height,
+                x_height,
+                cap_height,
+                underline_position,
+                underline_thickness,
+                strikeout_position,
+                strikeout_thickness,
+            }
+        }
+    }
+
+    pub fn is_scalable(&self) -> bool {
+        unsafe { (*self.face).face_flags & FT_FACE_FLAG_SCALABLE as FT_Long != 0 }
+    }
+
+    pub fn has_color(&self) -> bool {
+        unsafe { (*self.face).face_flags & FT_FACE_FLAG_COLOR as FT_Long != 0 }
+    }
+
+    /// Selects the smallest strike at least `pixel_size` high, or the
+    /// largest one available, and returns its size in pixels.
+    fn select_strike(&mut self, pixel_size: f64) -> Result<f64> {
+        let sizes = unsafe {
+            let face = &*self.face;
+            std::slice::from_raw_parts(face.available_sizes, face.num_fixed_sizes.max(0) as usize)
+        };
+        let strike_size = |i: usize| sizes[i].y_ppem as f64 / 64.0;
+        let best = (0..sizes.len())
+            .filter(|&i| strike_size(i) >= pixel_size)
+            .min_by(|&a, &b| strike_size(a).total_cmp(&strike_size(b)))
+            .or_else(|| (0..sizes.len()).max_by(|&a, &b| strike_size(a).total_cmp(&strike_size(b))))
+            .ok_or_else(|| anyhow!("face is neither scalable nor has bitmap strikes"))?;
+        ft_result(unsafe { FT_Select_Size(self.face, best as FT_Int) }, strike_size(best))
     }
 
     fn set_char_size(
@@ -90,18 +251,123 @@ impl Face {
         )
     }
 
+    /// Stroke width, in 26.6 pixels, used for synthetic bold; the same
+    /// amount `FT_GlyphSlot_Embolden` would use.
+    pub fn embolden_strength(&self) -> FT_Pos {
+        unsafe {
+            let face = &*self.face;
+            FT_MulFix(face.units_per_EM as FT_Long, (*face.size).metrics.y_scale) / 24
+        }
+    }
+
     pub fn load_and_render_glyph(
         &mut self,
         glyph_index: FT_UInt,
         load_flags: FT_Int32,
         render_mode: FT_Render_Mode,
+        synthesis: Synthesis,
     ) -> Result<&FT_GlyphSlotRec_> {
+        let strength = self.embolden_strength();
         unsafe {
             let res = FT_Load_Glyph(self.face, glyph_index, load_flags);
             let slot = ft_result(res, &mut *(*self.face).glyph)?;
+            if slot.format == FT_Glyph_Format::FT_GLYPH_FORMAT_OUTLINE {
+                if synthesis.bold {
+                    ft_result(FT_Outline_Embolden(&mut slot.outline, strength), ())?;
+                }
+                if synthesis.italic {
+                    FT_Outline_Transform(&slot.outline, &OBLIQUE_MATRIX);
+                }
+            }
             ft_result(FT_Render_Glyph(slot, render_mode), slot)
         }
     }
+
+    /// Renders the glyph outline grown by `radius` 26.6 pixels on its
+    /// outside, with FreeType's stroker. Glyphs without an outline, such
+    /// as color bitmaps, have no stroke.
+    pub fn load_and_stroke_glyph(
+        &mut self,
+        glyph_index: FT_UInt,
+        load_flags: FT_Int32,
+        render_mode: FT_Render_Mode,
+        synthesis: Synthesis,
+        radius: FT_Fixed,
+    ) -> Result<Option<Glyph>> {
+        let strength = self.embolden_strength();
+        let stroker = Stroker::new(self.lib, radius)?;
+        unsafe {
+            let res = FT_Load_Glyph(self.face, glyph_index, load_flags | FT_LOAD_NO_BITMAP as i32);
+            let slot = ft_result(res, &mut *(*self.face).glyph)?;
+            if slot.format != FT_Glyph_Format::FT_GLYPH_FORMAT_OUTLINE {
+                return Ok(None);
+            }
+            if synthesis.bold {
+                ft_result(FT_Outline_Embolden(&mut slot.outline, strength), ())?;
+            }
+            if synthesis.italic {
+                FT_Outline_Transform(&slot.outline, &OBLIQUE_MATRIX);
+            }
+            let mut glyph = Glyph { glyph: ptr::null_mut() };
+            ft_result(FT_Get_Glyph(slot, &mut glyph.glyph), ())?;
+            // Both calls replace the glyph, freeing the previous one only
+            // when they succeed.
+            ft_result(FT_Glyph_StrokeBorder(&mut glyph.glyph, stroker.stroker, 0, 1), ())?;
+            ft_result(FT_Glyph_To_Bitmap(&mut glyph.glyph, render_mode, ptr::null(), 1), ())?;
+            Ok(Some(glyph))
+        }
+    }
+}
+
+/// A glyph copied out of the face's glyph slot, freed on drop.
+pub struct Glyph {
+    glyph: FT_Glyph,
+}
+
+impl Drop for Glyph {
+    fn drop(&mut self) {
+        unsafe {
+            FT_Done_Glyph(self.glyph);
+        }
+    }
+}
+
+impl Glyph {
+    /// Bitmap of a rendered glyph and its offset from the pen position,
+    /// as `bitmap_left` and `bitmap_top` are for a glyph slot.
+    pub fn bitmap(&self) -> Option<(&FT_Bitmap, FT_Int, FT_Int)> {
+        unsafe {
+            if (*self.glyph).format != FT_Glyph_Format::FT_GLYPH_FORMAT_BITMAP {
+                return None;
+            }
+            let bitmap_glyph = &*(self.glyph as *const FT_BitmapGlyphRec);
+            Some((&bitmap_glyph.bitmap, bitmap_glyph.left, bitmap_glyph.top))
+        }
+    }
+}
+
+struct Stroker {
+    stroker: FT_Stroker,
+}
+
+impl Drop for Stroker {
+    fn drop(&mut self) {
+        unsafe {
+            FT_Stroker_Done(self.stroker);
+        }
+    }
+}
+
+impl Stroker {
+    /// A stroker with round caps and joins, `radius` in 26.6 pixels.
+    fn new(lib: FT_Library, radius: FT_Fixed) -> Result<Self> {
+        let mut stroker = ptr::null_mut();
+        unsafe {
+            ft_result(FT_Stroker_New(lib, &mut stroker), ())?;
+            FT_Stroker_Set(stroker, radius, FT_STROKER_LINECAP_ROUND, FT_STROKER_LINEJOIN_ROUND, 0);
+        }
+        Ok(Self { stroker })
+    }
 }
 
 extern "C" fn alloc_library(_memory: FT_Memory, size: c_long) -> *mut c_void {
@@ -122,7 +388,7 @@ extern "C" fn realloc_library(
 }
 
 static mut MEMORY: FT_MemoryRec_ = FT_MemoryRec_ {
-    user: 0 as *mut c_void,
+    user: ptr::null_mut(),
     alloc: Some(alloc_library),
     free: Some(free_library),
     realloc: Some(realloc_library),
@@ -144,7 +410,7 @@ impl Library {
     pub fn new() -> Result<Library> {
         let mut lib = ptr::null_mut();
 
-        let err = unsafe { FT_New_Library(&mut MEMORY, &mut lib) };
+        let err = unsafe { FT_New_Library(ptr::addr_of_mut!(MEMORY), &mut lib) };
         if err == freetype::freetype::FT_Err_Ok as FT_Error {
             unsafe {
                 FT_Add_Default_Modules(lib);
@@ -4,7 +4,9 @@ pub use harfbuzz::*;
 use harfbuzz_sys as harfbuzz;
 
 use anyhow::{ensure, Result};
+use std::ffi::CStr;
 use std::mem;
+use std::os::raw::c_char;
 use std::slice;
 
 extern "C" {
@@ -72,6 +74,36 @@ impl Buffer {
         unsafe { hb_buffer_get_script(self.buf) }
     }
 
+    pub fn get_direction(&self) -> hb_direction_t {
+        unsafe { hb_buffer_get_direction(self.buf) }
+    }
+
+    pub fn get_language(&self) -> String {
+        unsafe {
+            let language = hb_buffer_get_language(self.buf);
+            if language.is_null() {
+                return String::new();
+            }
+            CStr::from_ptr(hb_language_to_string(language)).to_string_lossy().into_owned()
+        }
+    }
+
+    pub fn set_direction(&mut self, direction: hb_direction_t) {
+        unsafe { hb_buffer_set_direction(self.buf, direction) };
+    }
+
+    pub fn set_script(&mut self, script: hb_script_t) {
+        unsafe { hb_buffer_set_script(self.buf, script) };
+    }
+
+    pub fn set_language(&mut self, language: &str) {
+        unsafe {
+            let language =
+                hb_language_from_string(language.as_ptr() as *const c_char, language.len() as i32);
+            hb_buffer_set_language(self.buf, language)
+        };
+    }
+
     pub fn add_utf8(&mut self, buf: &[u8]) {
         unsafe {
             hb_buffer_add_utf8(
@@ -16,3 +16,11 @@ pub struct FontDataHandle {
     pub data: Vec<u8>,
     pub index: u32,
 }
+
+/// Styles that the matched face lacks and that have to be emulated
+/// when rasterizing and shaping.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Synthesis {
+    pub bold: bool,
+    pub italic: bool,
+}
@@ -1,8 +1,8 @@
-use crate::font::loader::{FontDataHandle, Names};
+use crate::font::loader::{FontDataHandle, Names, Synthesis};
 use crate::input::FontAttributes;
 use anyhow::{bail, Result};
 
-pub fn load_built_in_font(font_attributes: &FontAttributes) -> Result<FontDataHandle> {
+pub fn load_built_in_font(font_attributes: &FontAttributes) -> Result<(FontDataHandle, Synthesis)> {
     let mut font_info = vec![];
     load_built_in_fonts(&mut font_info).ok();
     match_font_info(font_attributes, font_info)
@@ -11,12 +11,22 @@ pub fn load_built_in_font(font_attributes: &FontAttributes) -> Result<FontDataHa
 fn match_font_info(
     attr: &FontAttributes,
     mut font_info: Vec<(Names, FontDataHandle)>,
-) -> Result<FontDataHandle> {
+) -> Result<(FontDataHandle, Synthesis)> {
     font_info.sort_by_key(|(names, _)| names.full_name.clone());
 
-    for (names, handle) in &font_info {
-        if font_info_matches(attr, names) {
-            return Ok(handle.clone());
+    // Prefer a real face, then the closest face of the family with the
+    // missing styles synthesized by the rasterizer.
+    for (bold, italic) in
+        &[(attr.bold, attr.italic), (attr.bold, false), (false, attr.italic), (false, false)]
+    {
+        let candidate =
+            FontAttributes { family: attr.family.clone(), bold: *bold, italic: *italic };
+        for (names, handle) in &font_info {
+            if font_info_matches(&candidate, names) {
+                let synthesis =
+                    Synthesis { bold: attr.bold && !bold, italic: attr.italic && !italic };
+                return Ok((handle.clone(), synthesis));
+            }
         }
     }
     bail!("Could not find font");
@@ -1,4 +1,5 @@
-use std::cell::RefCell;
+use lru::LruCache;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -6,33 +7,109 @@ pub mod ftwrap;
 pub mod hbwrap;
 pub mod loader;
 pub mod rasterizer;
+pub mod segment;
 pub mod shaper;
 
 use crate::font::loader::parser::load_built_in_font;
 use crate::font::rasterizer::FontRasterizer;
 pub use crate::font::rasterizer::{FontMetrics, RasterizedGlyph};
-use crate::font::shaper::FontShaper;
+use crate::font::segment::{Orientation, Presentation};
 pub use crate::font::shaper::GlyphInfo;
-use crate::input::TextStyle;
+use crate::font::shaper::{FontShaper, ShapeProperties};
+use crate::input::{Direction, FontAttributes};
+use crate::language;
+use crate::utils::PixelLength;
 use anyhow::Result;
 
+const SHAPE_CACHE_SIZE: usize = 1024;
+
+/// Vertical alternates for upright glyphs in vertical text.
+const VERTICAL_FEATURES: &[&str] = &["vert", "vrt2"];
+
 pub struct LoadedFont {
     rasterizer: Box<dyn FontRasterizer>,
     shaper: Box<dyn FontShaper>,
+    metrics: FontMetrics,
 }
 
 impl LoadedFont {
-    pub fn shape(&self, text: &str) -> Result<Vec<GlyphInfo>> {
-        self.shaper.shape(text)
+    pub fn shape(&self, text: &str, properties: &ShapeProperties) -> Result<Vec<GlyphInfo>> {
+        self.shaper.shape(text, properties)
     }
 
     pub fn rasterize(&self, glyph_pos: u32) -> Result<RasterizedGlyph> {
         self.rasterizer.rasterize(glyph_pos)
     }
+
+    pub fn rasterize_stroke(
+        &self,
+        glyph_pos: u32,
+        width: PixelLength,
+    ) -> Result<Option<RasterizedGlyph>> {
+        self.rasterizer.rasterize_stroke(glyph_pos, width)
+    }
+
+    pub fn metrics(&self) -> FontMetrics {
+        self.metrics
+    }
+}
+
+/// Glyphs of a word shaped with a single font, in visual order.
+pub struct GlyphRun {
+    pub font: Rc<LoadedFont>,
+    pub glyphs: Vec<GlyphInfo>,
+    pub properties: ShapeProperties,
+}
+
+impl GlyphRun {
+    /// Whether the run was shaped top to bottom.
+    pub fn is_vertical(&self) -> bool {
+        self.properties.direction == hbwrap::HB_DIRECTION_TTB
+    }
+
+    /// Advance of `glyph` along the direction the run progresses in.
+    /// HarfBuzz's y axis points up, so vertical advances are negative.
+    pub fn advance(&self, glyph: &GlyphInfo) -> PixelLength {
+        if self.is_vertical() {
+            -glyph.y_advance
+        } else {
+            glyph.x_advance
+        }
+    }
+
+    /// Lengthens the advance of `glyph` along the run's direction.
+    pub fn add_advance(&mut self, index: usize, extra: PixelLength) {
+        if self.is_vertical() {
+            self.glyphs[index].y_advance -= extra;
+        } else {
+            self.glyphs[index].x_advance += extra;
+        }
+    }
+}
+
+/// Segment properties are guessed from the text, so only the direction
+/// forced on a run, if any, needs to be part of the key: hits do no
+/// HarfBuzz work at all.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ShapeKey {
+    /// Only what picks the font: colors don't change the shaping.
+    font: FontAttributes,
+    text: String,
+    direction: Option<hbwrap::hb_direction_t>,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
 }
 
 pub struct FontConfiguration {
-    fonts: RefCell<HashMap<TextStyle, Rc<LoadedFont>>>,
+    /// Colors don't change the face, so fonts are loaded once per
+    /// family and style.
+    fonts: RefCell<HashMap<FontAttributes, Rc<LoadedFont>>>,
+    shape_cache: RefCell<LruCache<ShapeKey, (ShapeProperties, Vec<GlyphInfo>)>>,
+    shape_cache_stats: Cell<CacheStats>,
     font_size: f64,
     dpi: u32,
     lib: ftwrap::Library,
@@ -41,23 +118,144 @@ pub struct FontConfiguration {
 impl FontConfiguration {
     pub fn new(font_size: f64, dpi: u32) -> Result<Self> {
         let lib = ftwrap::Library::new()?;
-        Ok(Self { fonts: RefCell::new(HashMap::new()), font_size, dpi, lib })
+        Ok(Self {
+            fonts: RefCell::new(HashMap::new()),
+            shape_cache: RefCell::new(LruCache::new(SHAPE_CACHE_SIZE)),
+            shape_cache_stats: Cell::new(CacheStats::default()),
+            font_size,
+            dpi,
+            lib,
+        })
     }
 
-    pub fn get_font(&self, style: &TextStyle) -> Result<Rc<LoadedFont>> {
+    pub fn get_font(&self, font_attributes: &FontAttributes) -> Result<Rc<LoadedFont>> {
         let mut fonts = self.fonts.borrow_mut();
-        if let Some(entry) = fonts.get(style) {
+        if let Some(entry) = fonts.get(font_attributes) {
             return Ok(Rc::clone(entry));
         }
-        let font_data_handle = load_built_in_font(&style.font_attributes)?;
+        let (font_data_handle, synthesis) = load_built_in_font(font_attributes)?;
         let mut face = self.lib.new_face(&font_data_handle)?;
-        face.set_font_size(self.font_size, self.dpi)?;
-        let shaper = shaper::new_shaper(&face)?;
-        let rasterizer = rasterizer::new_rasterizer(&face)?;
-        let loaded = Rc::new(LoadedFont { rasterizer, shaper });
+        let scale = face.set_font_size(self.font_size, self.dpi)?;
+        let shaper = shaper::new_shaper(&face, synthesis, scale)?;
+        let rasterizer = rasterizer::new_rasterizer(&face, synthesis, scale)?;
+        let metrics = rasterizer.metrics();
+        let loaded = Rc::new(LoadedFont { rasterizer, shaper, metrics });
 
-        fonts.insert(style.clone(), Rc::clone(&loaded));
+        fonts.insert(font_attributes.clone(), Rc::clone(&loaded));
 
         Ok(loaded)
     }
+
+    /// Shapes `text`, falling back to the emoji font for grapheme clusters
+    /// with emoji presentation. Cluster values are byte offsets in `text`.
+    /// In vertical text, upright runs are shaped top to bottom and the
+    /// others horizontally, to be rotated by the layout.
+    pub fn shape(
+        &self,
+        text: &str,
+        font_attributes: &FontAttributes,
+        direction: Option<Direction>,
+    ) -> Result<Vec<GlyphRun>> {
+        let mut runs = Vec::new();
+        for run in segment::split_runs(text) {
+            let (attributes, font) = match run.presentation {
+                Presentation::Text => (font_attributes.clone(), self.get_font(font_attributes)?),
+                Presentation::Emoji => {
+                    // Bitmap emoji are never emboldened or slanted, so
+                    // they mustn't get the synthetic advance and skew.
+                    let emoji_attributes = FontAttributes {
+                        family: language::EMOJI_FONT.into(),
+                        bold: false,
+                        italic: false,
+                    };
+                    // Without the emoji font, the word's own font draws
+                    // what it can of them.
+                    match self.get_font(&emoji_attributes) {
+                        Ok(font) => (emoji_attributes, font),
+                        Err(_) => (font_attributes.clone(), self.get_font(font_attributes)?),
+                    }
+                }
+            };
+            let pieces = match direction {
+                Some(Direction::Ttb) => segment::split_orientations(&text[run.range.clone()])
+                    .into_iter()
+                    .map(|(range, o)| {
+                        (run.range.start + range.start..run.range.start + range.end, o)
+                    })
+                    .collect(),
+                _ => vec![(run.range.clone(), Orientation::Rotated)],
+            };
+            for (range, orientation) in pieces {
+                let forced = match direction {
+                    Some(Direction::Ttb) if orientation == Orientation::Upright => {
+                        Some(hbwrap::HB_DIRECTION_TTB)
+                    }
+                    Some(direction @ (Direction::Ltr | Direction::Rtl)) => {
+                        Some(direction.to_harfbuzz())
+                    }
+                    _ => None,
+                };
+                let (properties, mut glyphs) =
+                    self.shape_cached(&font, &attributes, &text[range.clone()], forced)?;
+                for glyph in glyphs.iter_mut() {
+                    glyph.cluster += range.start as u32;
+                }
+                runs.push(GlyphRun { font: Rc::clone(&font), glyphs, properties });
+            }
+        }
+
+        // As HarfBuzz would guess it for the whole text: the direction of
+        // the first run it found a script in.
+        let rtl = match direction {
+            Some(direction) => direction == Direction::Rtl,
+            None => {
+                let scripted =
+                    runs.iter().find(|run| run.properties.script != hbwrap::HB_SCRIPT_INVALID);
+                scripted.map(|run| run.properties.direction) == Some(hbwrap::HB_DIRECTION_RTL)
+            }
+        };
+        if rtl {
+            runs.reverse();
+        }
+        Ok(runs)
+    }
+
+    fn shape_cached(
+        &self,
+        font: &LoadedFont,
+        font_attributes: &FontAttributes,
+        text: &str,
+        direction: Option<hbwrap::hb_direction_t>,
+    ) -> Result<(ShapeProperties, Vec<GlyphInfo>)> {
+        let key = ShapeKey { font: font_attributes.clone(), text: text.to_string(), direction };
+        let mut stats = self.shape_cache_stats.get();
+        let mut cache = self.shape_cache.borrow_mut();
+        if let Some(shaped) = cache.get(&key) {
+            stats.hits += 1;
+            self.shape_cache_stats.set(stats);
+            return Ok(shaped.clone());
+        }
+        stats.misses += 1;
+        self.shape_cache_stats.set(stats);
+
+        let mut properties = ShapeProperties::guess(text)?;
+        if let Some(direction) = direction {
+            properties.direction = direction;
+            if direction == hbwrap::HB_DIRECTION_TTB {
+                properties.features.extend(VERTICAL_FEATURES.iter().map(|f| f.to_string()));
+            }
+        }
+        let glyphs = font.shape(text, &properties)?;
+        cache.put(key, (properties.clone(), glyphs.clone()));
+        Ok((properties, glyphs))
+    }
+
+    /// Size of an em in pixels, the unit of em-relative lengths.
+    pub fn pixel_size(&self) -> f64 {
+        self.font_size * self.dpi as f64 / 72.0
+    }
+
+    pub fn shape_cache_stats(&self) -> CacheStats {
+        self.shape_cache_stats.get()
+    }
 }
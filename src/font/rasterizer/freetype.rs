@@ -1,40 +1,86 @@
-use crate::font::rasterizer::FontRasterizer;
+use crate::font::loader::Synthesis;
+use crate::font::rasterizer::{FontMetrics, FontRasterizer};
 use crate::font::{ftwrap, RasterizedGlyph};
 use crate::utils::PixelLength;
 use anyhow::Result;
-use freetype::freetype::FT_GlyphSlotRec_;
+use freetype::freetype::{FT_Bitmap, FT_Fixed, FT_Int, FT_Pixel_Mode, FT_Render_Mode};
 use std::cell::RefCell;
 use std::slice;
 
 pub struct FreeTypeRasterizer {
     face: RefCell<ftwrap::Face>,
+    synthesis: Synthesis,
+    has_color: bool,
+    scale: f64,
 }
 
 impl FontRasterizer for FreeTypeRasterizer {
     fn rasterize(&self, glyph_pos: u32) -> Result<RasterizedGlyph> {
-        let (load_flags, render_mode) = ftwrap::compute_load_flags();
+        let (load_flags, render_mode) = ftwrap::compute_load_flags(self.has_color);
 
         let mut face = self.face.borrow_mut();
-        let ft_glyph = face.load_and_render_glyph(glyph_pos, load_flags, render_mode)?;
+        let ft_glyph =
+            face.load_and_render_glyph(glyph_pos, load_flags, render_mode, self.synthesis)?;
 
-        let pitch = ft_glyph.bitmap.pitch.abs() as usize;
-        let data = unsafe {
-            slice::from_raw_parts_mut(ft_glyph.bitmap.buffer, ft_glyph.bitmap.rows as usize * pitch)
+        let glyph =
+            self.rasterize_bitmap(&ft_glyph.bitmap, ft_glyph.bitmap_left, ft_glyph.bitmap_top);
+        Ok(glyph.scale(self.scale))
+    }
+
+    fn rasterize_stroke(
+        &self,
+        glyph_pos: u32,
+        width: PixelLength,
+    ) -> Result<Option<RasterizedGlyph>> {
+        let render_mode = FT_Render_Mode::FT_RENDER_MODE_NORMAL;
+        let (load_flags, _) = ftwrap::compute_load_flags(false);
+        // The stroke is drawn at the requested size, the radius is given
+        // in the strike's pixels.
+        let radius = (width.get() / self.scale * 64.0).round() as FT_Fixed;
+
+        let mut face = self.face.borrow_mut();
+        let stroked =
+            face.load_and_stroke_glyph(glyph_pos, load_flags, render_mode, self.synthesis, radius)?;
+        let glyph = match stroked.as_ref().and_then(|glyph| glyph.bitmap()) {
+            Some((bitmap, left, top)) => self.rasterize_bitmap(bitmap, left, top),
+            None => return Ok(None),
         };
+        Ok(Some(glyph.scale(self.scale)))
+    }
 
-        let glyph = self.rasterize(pitch, ft_glyph, data);
-        Ok(glyph)
+    fn metrics(&self) -> FontMetrics {
+        self.face.borrow().metrics().scale(self.scale)
     }
 }
 
 impl FreeTypeRasterizer {
-    fn rasterize(&self, pitch: usize, ft_glyph: &FT_GlyphSlotRec_, data: &[u8]) -> RasterizedGlyph {
-        let width = ft_glyph.bitmap.width as usize / 3;
-        let height = ft_glyph.bitmap.rows as usize;
-        let size = (width * height * 4) as usize;
+    fn rasterize_bitmap(&self, bitmap: &FT_Bitmap, left: FT_Int, top: FT_Int) -> RasterizedGlyph {
+        let pitch = bitmap.pitch.unsigned_abs() as usize;
+        let data = unsafe { slice::from_raw_parts(bitmap.buffer, bitmap.rows as usize * pitch) };
+
+        let glyph = match bitmap.pixel_mode {
+            mode if mode == FT_Pixel_Mode::FT_PIXEL_MODE_LCD as u8 => {
+                self.rasterize_lcd(pitch, bitmap, data)
+            }
+            mode if mode == FT_Pixel_Mode::FT_PIXEL_MODE_BGRA as u8 => {
+                self.rasterize_bgra(pitch, bitmap, data)
+            }
+            _ => self.rasterize_gray(pitch, bitmap, data),
+        };
+        RasterizedGlyph {
+            left: PixelLength::new(left as f64),
+            top: PixelLength::new(top as f64),
+            ..glyph
+        }
+    }
+
+    fn rasterize_lcd(&self, pitch: usize, bitmap: &FT_Bitmap, data: &[u8]) -> RasterizedGlyph {
+        let width = bitmap.width as usize / 3;
+        let height = bitmap.rows as usize;
+        let size = width * height * 4;
         let mut rgba = vec![0u8; size];
         for y in 0..height {
-            let src_offset = y * pitch as usize;
+            let src_offset = y * pitch;
             let dest_offset = y * width * 4;
             for x in 0..width {
                 let red = data[src_offset + (x * 3)];
@@ -51,13 +97,76 @@ impl FreeTypeRasterizer {
             data: rgba,
             height,
             width,
-            left: PixelLength::new(ft_glyph.bitmap_left as f64),
-            top: PixelLength::new(ft_glyph.bitmap_top as f64),
+            left: PixelLength::new(0.),
+            top: PixelLength::new(0.),
+            has_color: false,
+        }
+    }
+
+    fn rasterize_gray(&self, pitch: usize, bitmap: &FT_Bitmap, data: &[u8]) -> RasterizedGlyph {
+        let width = bitmap.width as usize;
+        let height = bitmap.rows as usize;
+        let size = width * height * 4;
+        let mut rgba = vec![0u8; size];
+        for y in 0..height {
+            let src_offset = y * pitch;
+            let dest_offset = y * width * 4;
+            for x in 0..width {
+                let coverage = data[src_offset + x];
+                rgba[dest_offset + (x * 4)..dest_offset + (x * 4) + 4].fill(coverage);
+            }
+        }
+        RasterizedGlyph {
+            data: rgba,
+            height,
+            width,
+            left: PixelLength::new(0.),
+            top: PixelLength::new(0.),
+            has_color: false,
+        }
+    }
+
+    fn rasterize_bgra(&self, pitch: usize, bitmap: &FT_Bitmap, data: &[u8]) -> RasterizedGlyph {
+        let width = bitmap.width as usize;
+        let height = bitmap.rows as usize;
+        let size = width * height * 4;
+        let mut rgba = vec![0u8; size];
+        for y in 0..height {
+            let src_offset = y * pitch;
+            let dest_offset = y * width * 4;
+            for x in 0..width {
+                let blue = data[src_offset + (x * 4)];
+                let green = data[src_offset + (x * 4) + 1];
+                let red = data[src_offset + (x * 4) + 2];
+                let alpha = data[src_offset + (x * 4) + 3];
+                // FreeType hands out premultiplied sRGB; the atlas takes
+                // color bitmaps as straight sRGB.
+                let unpremultiply = |c: u8| {
+                    if alpha == 0 {
+                        0
+                    } else {
+                        ((c as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8
+                    }
+                };
+                rgba[dest_offset + (x * 4)] = unpremultiply(red);
+                rgba[dest_offset + (x * 4) + 1] = unpremultiply(green);
+                rgba[dest_offset + (x * 4) + 2] = unpremultiply(blue);
+                rgba[dest_offset + (x * 4) + 3] = alpha;
+            }
+        }
+        RasterizedGlyph {
+            data: rgba,
+            height,
+            width,
+            left: PixelLength::new(0.),
+            top: PixelLength::new(0.),
+            has_color: true,
         }
     }
 
-    pub fn new(face: &ftwrap::Face) -> Result<Self> {
+    pub fn new(face: &ftwrap::Face, synthesis: Synthesis, scale: f64) -> Result<Self> {
         let cloned_face = face.clone();
-        Ok(Self { face: RefCell::new(cloned_face) })
+        let has_color = face.has_color();
+        Ok(Self { face: RefCell::new(cloned_face), synthesis, has_color, scale })
     }
 }
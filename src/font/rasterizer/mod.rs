@@ -1,4 +1,5 @@
 use crate::font::ftwrap;
+use crate::font::loader::Synthesis;
 use crate::utils::PixelLength;
 use anyhow::Result;
 
@@ -10,21 +11,122 @@ pub struct RasterizedGlyph {
     pub width: usize,
     pub top: PixelLength,
     pub left: PixelLength,
+    /// The bitmap carries its own colors (color emoji) and must not be
+    /// tinted with the foreground color.
+    pub has_color: bool,
 }
 
+impl RasterizedGlyph {
+    /// Resamples the bitmap by `factor`, used to bring fixed-size bitmap
+    /// strikes to the requested font size.
+    pub fn scale(self, factor: f64) -> Self {
+        if (factor - 1.0).abs() < f64::EPSILON || self.width == 0 || self.height == 0 {
+            return self;
+        }
+        let width = ((self.width as f64 * factor).round() as usize).max(1);
+        let height = ((self.height as f64 * factor).round() as usize).max(1);
+        let mut data = vec![0u8; width * height * 4];
+
+        // Box filter over premultiplied pixels: strikes are usually
+        // downscaled, where bilinear sampling would alias.
+        let (sx, sy) = (self.width as f64 / width as f64, self.height as f64 / height as f64);
+        for y in 0..height {
+            let (y0, y1) = (
+                (y as f64 * sy) as usize,
+                (((y + 1) as f64 * sy).ceil() as usize).min(self.height),
+            );
+            for x in 0..width {
+                let (x0, x1) = (
+                    (x as f64 * sx) as usize,
+                    (((x + 1) as f64 * sx).ceil() as usize).min(self.width),
+                );
+                let mut acc = [0f64; 4];
+                let mut n = 0f64;
+                for src_y in y0..y1.max(y0 + 1) {
+                    for src_x in x0..x1.max(x0 + 1) {
+                        let p = &self.data[(src_y * self.width + src_x) * 4..][..4];
+                        let a = p[3] as f64 / 255.;
+                        acc[0] += p[0] as f64 * a;
+                        acc[1] += p[1] as f64 * a;
+                        acc[2] += p[2] as f64 * a;
+                        acc[3] += p[3] as f64;
+                        n += 1.;
+                    }
+                }
+                let alpha = acc[3] / n;
+                let dest = &mut data[(y * width + x) * 4..][..4];
+                if alpha > 0. {
+                    let unpremultiply = 255. / alpha;
+                    for c in 0..3 {
+                        dest[c] = (acc[c] / n * unpremultiply).round().min(255.) as u8;
+                    }
+                }
+                dest[3] = alpha.round() as u8;
+            }
+        }
+
+        RasterizedGlyph {
+            data,
+            width,
+            height,
+            top: self.top * factor,
+            left: self.left * factor,
+            has_color: self.has_color,
+        }
+    }
+}
+
+/// Vertical metrics of a face at its rasterized size. Positions are
+/// measured from the baseline, positive upwards as in FreeType, so the
+/// descender and the underline position are usually negative.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct FontMetrics {
-    pub cell_width: PixelLength,
-    pub cell_height: PixelLength,
+    pub ascender: PixelLength,
     pub descender: PixelLength,
-    pub underline_thickness: PixelLength,
+    pub line_gap: PixelLength,
+    /// Baseline-to-baseline distance.
+    pub line_height: PixelLength,
+    pub x_height: PixelLength,
+    pub cap_height: PixelLength,
     pub underline_position: PixelLength,
+    pub underline_thickness: PixelLength,
+    pub strikeout_position: PixelLength,
+    pub strikeout_thickness: PixelLength,
+}
+
+impl FontMetrics {
+    pub fn scale(self, factor: f64) -> Self {
+        Self {
+            ascender: self.ascender * factor,
+            descender: self.descender * factor,
+            line_gap: self.line_gap * factor,
+            line_height: self.line_height * factor,
+            x_height: self.x_height * factor,
+            cap_height: self.cap_height * factor,
+            underline_position: self.underline_position * factor,
+            underline_thickness: self.underline_thickness * factor,
+            strikeout_position: self.strikeout_position * factor,
+            strikeout_thickness: self.strikeout_thickness * factor,
+        }
+    }
 }
 
 pub trait FontRasterizer {
     fn rasterize(&self, glyph_pos: u32) -> Result<RasterizedGlyph>;
+    /// Coverage of the glyph grown by `width` on its outside, drawn under
+    /// the glyph to outline it; `None` when the glyph has no outline.
+    fn rasterize_stroke(
+        &self,
+        glyph_pos: u32,
+        width: PixelLength,
+    ) -> Result<Option<RasterizedGlyph>>;
+    fn metrics(&self) -> FontMetrics;
 }
 
-pub fn new_rasterizer(face: &ftwrap::Face) -> Result<Box<dyn FontRasterizer>> {
-    Ok(Box::new(freetype::FreeTypeRasterizer::new(face)?))
+pub fn new_rasterizer(
+    face: &ftwrap::Face,
+    synthesis: Synthesis,
+    scale: f64,
+) -> Result<Box<dyn FontRasterizer>> {
+    Ok(Box::new(freetype::FreeTypeRasterizer::new(face, synthesis, scale)?))
 }
@@ -0,0 +1,120 @@
+use std::ops::Range;
+use unic_emoji_char::{is_emoji, is_emoji_modifier, is_emoji_presentation};
+use unicode_segmentation::UnicodeSegmentation;
+
+const TEXT_PRESENTATION_SELECTOR: char = '\u{FE0E}';
+const EMOJI_PRESENTATION_SELECTOR: char = '\u{FE0F}';
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+const COMBINING_ENCLOSING_KEYCAP: char = '\u{20E3}';
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Upright,
+    /// Set sideways, turned 90° clockwise, in vertical text.
+    Rotated,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Presentation {
+    Text,
+    Emoji,
+}
+
+/// A byte range of a word whose grapheme clusters all share the same
+/// presentation, and therefore the same font.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Run {
+    pub range: Range<usize>,
+    pub presentation: Presentation,
+}
+
+/// Splits `text` into runs of extended grapheme clusters, so that ZWJ
+/// sequences, flags and modifier sequences never straddle two fonts.
+pub fn split_runs(text: &str) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for (start, grapheme) in text.grapheme_indices(true) {
+        let presentation = presentation(grapheme);
+        let end = start + grapheme.len();
+        match runs.last_mut() {
+            Some(run) if run.presentation == presentation => run.range.end = end,
+            _ => runs.push(Run { range: start..end, presentation }),
+        }
+    }
+    runs
+}
+
+/// Resolves the presentation of a grapheme cluster following UTS #51:
+/// variation selectors win, then sequences that only exist as emoji,
+/// then the default presentation of the base character.
+pub fn presentation(grapheme: &str) -> Presentation {
+    let base = match grapheme.chars().next() {
+        Some(c) => c,
+        None => return Presentation::Text,
+    };
+    if grapheme.contains(TEXT_PRESENTATION_SELECTOR) || !is_emoji(base) {
+        return Presentation::Text;
+    }
+    let is_sequence = grapheme.chars().skip(1).any(|c| {
+        c == EMOJI_PRESENTATION_SELECTOR
+            || c == ZERO_WIDTH_JOINER
+            || c == COMBINING_ENCLOSING_KEYCAP
+            || is_emoji_modifier(c)
+    });
+    if is_sequence || is_emoji_presentation(base) {
+        Presentation::Emoji
+    } else {
+        Presentation::Text
+    }
+}
+
+/// Splits `text` into ranges of grapheme clusters sharing the same
+/// orientation in vertical text.
+pub fn split_orientations(text: &str) -> Vec<(Range<usize>, Orientation)> {
+    let mut ranges: Vec<(Range<usize>, Orientation)> = Vec::new();
+    for (start, grapheme) in text.grapheme_indices(true) {
+        let orientation = orientation(grapheme);
+        let end = start + grapheme.len();
+        match ranges.last_mut() {
+            Some((range, o)) if *o == orientation => range.end = end,
+            _ => ranges.push((start..end, orientation)),
+        }
+    }
+    ranges
+}
+
+/// Approximates the Vertical_Orientation property of UAX #50 by block:
+/// CJK, fullwidth forms and emoji stand upright, everything else is
+/// rotated. Characters that only need a vertical alternate, such as CJK
+/// brackets, count as upright since `vert` substitutes them.
+pub fn orientation(grapheme: &str) -> Orientation {
+    let base = match grapheme.chars().next() {
+        Some(c) => c,
+        None => return Orientation::Rotated,
+    };
+    if presentation(grapheme) == Presentation::Emoji {
+        return Orientation::Upright;
+    }
+    match base as u32 {
+        0x00A7
+        | 0x00A9
+        | 0x00AE
+        | 0x00B1
+        | 0x00BC..=0x00BE
+        | 0x00D7
+        | 0x00F7
+        | 0x1100..=0x11FF
+        | 0x2E80..=0x2FFF
+        | 0x3000..=0x4DBF
+        | 0x4DC0..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xA960..=0xA97F
+        | 0xAC00..=0xD7FF
+        | 0xE000..=0xFAFF
+        | 0xFE10..=0xFE1F
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFFEF
+        | 0x1F000..=0x1FAFF
+        | 0x20000..=0x3FFFF => Orientation::Upright,
+        _ => Orientation::Rotated,
+    }
+}
@@ -1,6 +1,7 @@
 use crate::font::ftwrap;
 use crate::font::hbwrap as harfbuzz;
-use crate::font::shaper::{FontShaper, GlyphInfo};
+use crate::font::loader::Synthesis;
+use crate::font::shaper::{FontShaper, GlyphInfo, ShapeProperties};
 use crate::utils::PixelLength;
 use anyhow::Result;
 use std::cell::RefCell;
@@ -8,34 +9,59 @@ use std::cell::RefCell;
 #[derive(Clone)]
 struct Info<'a> {
     codepoint: harfbuzz::hb_codepoint_t,
+    cluster: u32,
     pos: &'a harfbuzz::hb_glyph_position_t,
 }
 
-fn make_glyphinfo(info: &Info) -> GlyphInfo {
+fn make_glyphinfo(info: &Info, scale: f64) -> GlyphInfo {
     GlyphInfo {
         glyph_pos: info.codepoint,
-        x_advance: PixelLength::new(f64::from(info.pos.x_advance) / 64.0),
-        y_advance: PixelLength::new(f64::from(info.pos.y_advance) / 64.0),
-        x_offset: PixelLength::new(f64::from(info.pos.x_offset) / 64.0),
-        y_offset: PixelLength::new(f64::from(info.pos.y_offset) / 64.0),
+        cluster: info.cluster,
+        cluster_len: 0,
+        x_advance: PixelLength::new(f64::from(info.pos.x_advance) / 64.0 * scale),
+        y_advance: PixelLength::new(f64::from(info.pos.y_advance) / 64.0 * scale),
+        x_offset: PixelLength::new(f64::from(info.pos.x_offset) / 64.0 * scale),
+        y_offset: PixelLength::new(f64::from(info.pos.y_offset) / 64.0 * scale),
+    }
+}
+
+/// Glyphs come out in visual order, so a cluster ends where the next
+/// larger cluster value starts, or at the end of the text.
+fn set_cluster_lengths(glyphs: &mut [GlyphInfo], text_len: u32) {
+    let mut starts: Vec<u32> = glyphs.iter().map(|g| g.cluster).collect();
+    starts.sort_unstable();
+    starts.dedup();
+    for glyph in glyphs.iter_mut() {
+        let next = match starts.binary_search(&glyph.cluster) {
+            Ok(i) => starts.get(i + 1).copied().unwrap_or(text_len),
+            Err(_) => text_len,
+        };
+        glyph.cluster_len = next - glyph.cluster;
     }
 }
 
 pub struct HarfbuzzShaper {
     font: RefCell<harfbuzz::Font>,
+    /// Extra advance given to every glyph when bold is synthesized, so
+    /// that emboldened outlines don't collide.
+    embolden: PixelLength,
+    /// Factor from the selected bitmap strike to the requested size.
+    scale: f64,
 }
 
 impl FontShaper for HarfbuzzShaper {
-    fn shape(&self, text: &str) -> Result<Vec<GlyphInfo>> {
-        let features = vec![
-            harfbuzz::feature_from_string("kern")?,
-            harfbuzz::feature_from_string("liga")?,
-            harfbuzz::feature_from_string("clig")?,
-        ];
+    fn shape(&self, text: &str, properties: &ShapeProperties) -> Result<Vec<GlyphInfo>> {
+        let features = properties
+            .features
+            .iter()
+            .map(|f| harfbuzz::feature_from_string(f))
+            .collect::<Result<Vec<_>>>()?;
         let mut buf = harfbuzz::Buffer::new()?;
         buf.add_str(text);
 
-        buf.guess_segment_properties();
+        buf.set_direction(properties.direction);
+        buf.set_script(properties.script);
+        buf.set_language(&properties.language);
         let mut font = self.font.borrow_mut();
         font.shape(&mut buf, features.as_slice());
 
@@ -45,18 +71,29 @@ impl FontShaper for HarfbuzzShaper {
         let mut cluster = Vec::new();
 
         for (i, info) in hb_infos.iter().enumerate() {
-            let info = Info { codepoint: info.codepoint, pos: &positions[i] };
-            let glyph = make_glyphinfo(&info);
+            let info =
+                Info { codepoint: info.codepoint, cluster: info.cluster, pos: &positions[i] };
+            let mut glyph = make_glyphinfo(&info, self.scale);
+            if glyph.x_advance.get() != 0. {
+                glyph.x_advance += self.embolden;
+            }
             cluster.push(glyph);
         }
 
+        set_cluster_lengths(&mut cluster, text.len() as u32);
+
         Ok(cluster)
     }
 }
 
 impl HarfbuzzShaper {
-    pub fn new(face: &ftwrap::Face) -> Result<Self> {
+    pub fn new(face: &ftwrap::Face, synthesis: Synthesis, scale: f64) -> Result<Self> {
         let font = harfbuzz::Font::new(face.face);
-        Ok(Self { font: RefCell::new(font) })
+        let embolden = if synthesis.bold {
+            PixelLength::new(face.embolden_strength() as f64 / 64.0)
+        } else {
+            PixelLength::new(0.)
+        };
+        Ok(Self { font: RefCell::new(font), embolden, scale })
     }
 }
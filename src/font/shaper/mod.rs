@@ -1,4 +1,6 @@
 use crate::font::ftwrap;
+use crate::font::hbwrap;
+use crate::font::loader::Synthesis;
 use crate::utils::PixelLength;
 use anyhow::Result;
 
@@ -7,16 +9,52 @@ pub mod harfbuzz;
 #[derive(Clone, Debug)]
 pub struct GlyphInfo {
     pub glyph_pos: u32,
+    /// Byte offset of the first character of the cluster this glyph
+    /// belongs to, as reported by HarfBuzz.
+    pub cluster: u32,
+    /// Length in bytes of that cluster; every glyph of a ligature or of a
+    /// decomposed character shares the same cluster.
+    pub cluster_len: u32,
     pub x_advance: PixelLength,
     pub y_advance: PixelLength,
     pub x_offset: PixelLength,
     pub y_offset: PixelLength,
 }
 
+pub const DEFAULT_FEATURES: &[&str] = &["kern", "liga", "clig"];
+
+/// Segment properties and OpenType features a run is shaped with.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShapeProperties {
+    pub features: Vec<String>,
+    pub direction: hbwrap::hb_direction_t,
+    pub script: hbwrap::hb_script_t,
+    pub language: String,
+}
+
+impl ShapeProperties {
+    /// Properties HarfBuzz guesses from `text`, with the default features.
+    pub fn guess(text: &str) -> Result<Self> {
+        let mut buf = hbwrap::Buffer::new()?;
+        buf.add_str(text);
+        buf.guess_segment_properties();
+        Ok(Self {
+            features: DEFAULT_FEATURES.iter().map(|f| f.to_string()).collect(),
+            direction: buf.get_direction(),
+            script: buf.get_script(),
+            language: buf.get_language(),
+        })
+    }
+}
+
 pub trait FontShaper {
-    fn shape(&self, text: &str) -> Result<Vec<GlyphInfo>>;
+    fn shape(&self, text: &str, properties: &ShapeProperties) -> Result<Vec<GlyphInfo>>;
 }
 
-pub fn new_shaper(face: &ftwrap::Face) -> Result<Box<dyn FontShaper>> {
-    Ok(Box::new(harfbuzz::HarfbuzzShaper::new(face)?))
+pub fn new_shaper(
+    face: &ftwrap::Face,
+    synthesis: Synthesis,
+    scale: f64,
+) -> Result<Box<dyn FontShaper>> {
+    Ok(Box::new(harfbuzz::HarfbuzzShaper::new(face, synthesis, scale)?))
 }
@@ -5,6 +5,7 @@ use crate::utils::PixelLength;
 use anyhow::Result;
 use glium::texture::SrgbTexture2d;
 use glium::Display;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 pub struct GlyphTexture<T: Texture2d> {
@@ -12,11 +13,59 @@ pub struct GlyphTexture<T: Texture2d> {
     pub y_offset: PixelLength,
     pub bearing_x: PixelLength,
     pub bearing_y: PixelLength,
+    pub has_color: bool,
     pub texture: Sprite<T>,
 }
 
+/// What a glyph is rasterized as. Lengths are kept as the bits of their
+/// `f32` so that keys can be hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpriteKind {
+    Fill,
+    Stroke {
+        width: u32,
+    },
+    /// Grown by `spread` then blurred with a Gaussian of deviation `sigma`.
+    Blurred {
+        spread: u32,
+        sigma: u32,
+    },
+}
+
+/// A sprite of a glyph in the atlas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    /// Address of the loaded font. The font configuration loads one per
+    /// font attributes and keeps it for as long as the atlas, so words
+    /// that only differ in color share their sprites.
+    pub font: usize,
+    pub glyph_pos: u32,
+    pub kind: SpriteKind,
+}
+
+/// A blurred rounded box, the shadow of a background box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoxKey {
+    pub width: u32,
+    pub height: u32,
+    pub radius: u32,
+    pub sigma: u32,
+}
+
+struct CachedGlyph<T: Texture2d> {
+    bearing_x: PixelLength,
+    bearing_y: PixelLength,
+    has_color: bool,
+    texture: Sprite<T>,
+}
+
+/// Nothing is ever freed from the atlas, so sprites are uploaded once
+/// and reused by every word that draws them.
 pub struct GlyphAtlas<T: Texture2d> {
     pub atlas: Atlas<T>,
+    /// `None` for glyphs without a sprite, like strokes of blank glyphs.
+    glyphs: HashMap<GlyphKey, Option<CachedGlyph<T>>>,
+    boxes: HashMap<BoxKey, CachedGlyph<T>>,
 }
 
 impl GlyphAtlas<SrgbTexture2d> {
@@ -30,32 +79,72 @@ impl GlyphAtlas<SrgbTexture2d> {
         )?);
         let atlas = Atlas::new(&surface).expect("failed to create new texture atlas");
 
-        Ok(Self { atlas })
+        Ok(Self { atlas, glyphs: HashMap::new(), boxes: HashMap::new() })
     }
 }
 
 impl<T: Texture2d> GlyphAtlas<T> {
-    pub fn load_glyph(
+    /// The sprite `key` stands for, rasterized and uploaded the first
+    /// time only, placed for the shaped glyph `info`.
+    pub fn cached_glyph(
         &mut self,
-        glyph: RasterizedGlyph,
+        key: GlyphKey,
         info: &GlyphInfo,
-    ) -> Result<GlyphTexture<T>> {
-        let raw_im = Image::with_rgba32(
-            glyph.width as usize,
-            glyph.height as usize,
-            4 * glyph.width as usize,
-            &glyph.data,
-        );
-
-        let bearing_x = glyph.left;
-        let bearing_y = glyph.top;
-        let x_offset = info.x_offset;
-        let y_offset = info.y_offset;
-
-        let texture = self.atlas.allocate(&raw_im)?;
-
-        let glyph = GlyphTexture { texture, x_offset, y_offset, bearing_x, bearing_y };
+        rasterize: impl FnOnce() -> Result<Option<RasterizedGlyph>>,
+    ) -> Result<Option<GlyphTexture<T>>> {
+        if !self.glyphs.contains_key(&key) {
+            let cached = match rasterize()? {
+                Some(glyph) => Some(upload(&mut self.atlas, glyph)?),
+                None => None,
+            };
+            self.glyphs.insert(key, cached);
+        }
+        Ok(self.glyphs[&key].as_ref().map(|cached| GlyphTexture {
+            x_offset: info.x_offset,
+            y_offset: info.y_offset,
+            bearing_x: cached.bearing_x,
+            bearing_y: cached.bearing_y,
+            has_color: cached.has_color,
+            texture: cached.texture.clone(),
+        }))
+    }
 
-        Ok(glyph)
+    /// The sprite of a blurred box, rasterized and uploaded the first
+    /// time only; its bearings place it relative to the box.
+    pub fn cached_box(
+        &mut self,
+        key: BoxKey,
+        rasterize: impl FnOnce() -> RasterizedGlyph,
+    ) -> Result<GlyphTexture<T>> {
+        if !self.boxes.contains_key(&key) {
+            let bitmap = rasterize();
+            let cached = upload(&mut self.atlas, bitmap)?;
+            self.boxes.insert(key, cached);
+        }
+        let cached = &self.boxes[&key];
+        Ok(GlyphTexture {
+            x_offset: PixelLength::new(0.),
+            y_offset: PixelLength::new(0.),
+            bearing_x: cached.bearing_x,
+            bearing_y: cached.bearing_y,
+            has_color: cached.has_color,
+            texture: cached.texture.clone(),
+        })
     }
 }
+
+fn upload<T: Texture2d>(atlas: &mut Atlas<T>, glyph: RasterizedGlyph) -> Result<CachedGlyph<T>> {
+    // Color bitmaps keep the sRGB values FreeType gave, so that they are
+    // only quantized once.
+    let raw_im = if glyph.has_color {
+        Image::with_srgba32(glyph.width, glyph.height, 4 * glyph.width, &glyph.data)
+    } else {
+        Image::with_rgba32(glyph.width, glyph.height, 4 * glyph.width, &glyph.data)
+    };
+    Ok(CachedGlyph {
+        bearing_x: glyph.left,
+        bearing_y: glyph.top,
+        has_color: glyph.has_color,
+        texture: atlas.allocate(&raw_im)?,
+    })
+}
@@ -1,47 +1,441 @@
-use crate::color::RgbColor;
+use crate::animation::{self, Easing, GlyphAnimation, GlyphEffect, Transition, WordTransition};
+use crate::color::{
+    ColorScheme, ColorValue, GeneratedColors, Paint, PaintJson, Palette, RgbaColor,
+};
 use crate::font::hbwrap as harfbuzz;
+use crate::font::segment::{self, Presentation};
 use crate::language;
-use anyhow::Result;
-use serde::Deserialize;
+use crate::timeline::{Keyframe, Keyframes};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const DEFAULT_BG_PADDING: Padding = Padding::uniform(15.);
 
 #[derive(Debug, Deserialize, Clone)]
 struct InputJson {
     font_size: usize,
+    max_width: Option<f64>,
+    /// Colors words can refer to by name.
+    #[serde(default)]
+    palette: HashMap<String, String>,
+    /// Bundles of colors and font attributes words can refer to by name.
+    #[serde(default)]
+    styles: HashMap<String, StyleJson>,
+    /// Generates the colors words and their styles leave unset.
+    color_scheme: Option<ColorScheme>,
+    /// How the canvas turns into the next word's, for the words that
+    /// don't set it.
+    canvas_transition: Option<Transition>,
+    /// Entrance and exit of the words that don't set theirs.
+    transition_in: Option<WordTransition>,
+    transition_out: Option<WordTransition>,
+    /// Per-glyph animation of the words that don't set theirs.
+    glyph_animation: Option<GlyphAnimationJson>,
+    /// How long the words that don't set it are shown, in milliseconds.
+    duration_ms: Option<u64>,
+    /// Animation of the whole scene, timed from the first word.
+    #[serde(default)]
+    keyframes: Vec<KeyframeJson>,
     words: Vec<WordJson>,
 }
 
+/// Defaults for the words that refer to it, which can override any of
+/// them.
+#[derive(Debug, Deserialize, Clone)]
+struct StyleJson {
+    canvas_color: Option<PaintJson>,
+    fg_color: Option<PaintJson>,
+    bg_color: Option<PaintJson>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 struct WordJson {
     text: String,
-    canvas_color: String,
-    fg_color: String,
-    bg_color: Option<String>,
+    style: Option<String>,
+    canvas_color: Option<PaintJson>,
+    fg_color: Option<PaintJson>,
+    bg_color: Option<PaintJson>,
     bold: Option<bool>,
     italic: Option<bool>,
+    align: Option<Align>,
+    valign: Option<VAlign>,
+    position: Option<(f32, f32)>,
+    underline: Option<DecorationStyle>,
+    strikethrough: Option<DecorationStyle>,
+    overline: Option<DecorationStyle>,
+    decoration_color: Option<String>,
+    letter_spacing: Option<Length>,
+    word_spacing: Option<Length>,
+    direction: Option<Direction>,
+    stroke_color: Option<String>,
+    stroke_width: Option<Length>,
+    shadow: Option<ShadowJson>,
+    glow: Option<ShadowJson>,
+    bg_shadow: Option<ShadowJson>,
+    bg_glow: Option<ShadowJson>,
+    bg_padding: Option<Padding>,
+    bg_radius: Option<f32>,
+    bg_fit: Option<BoxFit>,
+    border_width: Option<f32>,
+    border_color: Option<String>,
+    canvas_transition: Option<Transition>,
+    transition_in: Option<WordTransition>,
+    transition_out: Option<WordTransition>,
+    glyph_animation: Option<GlyphAnimationJson>,
+    duration_ms: Option<u64>,
+    #[serde(default)]
+    keyframes: Vec<KeyframeJson>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct ShadowJson {
+    offset: Option<(f32, f32)>,
+    blur: Option<f32>,
+    spread: Option<f32>,
+    color: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct KeyframeJson {
+    at_ms: u64,
+    #[serde(default)]
+    easing: Easing,
+    translate: Option<(f32, f32)>,
+    scale: Option<f32>,
+    rotate: Option<f32>,
+    opacity: Option<f32>,
+    fg_color: Option<String>,
+    canvas_color: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct GlyphAnimationJson {
+    effect: GlyphEffect,
+    duration_ms: Option<u64>,
+    #[serde(default)]
+    easing: Easing,
+    stagger_ms: Option<u64>,
+    amplitude: Option<f32>,
+    color: Option<String>,
 }
 
 pub struct Input {
     pub config: Config,
     pub words: Vec<Word>,
+    /// Animation of the whole scene.
+    pub keyframes: Keyframes,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub font_size: f64,
     pub dpi: u32,
+    /// Width at which text wraps onto a new line, the window width by default.
+    pub max_width: Option<f64>,
 }
 
 #[derive(Clone)]
 pub struct Word {
     pub text: String,
-    pub canvas_color: RgbColor,
+    pub canvas_color: Paint,
     pub style: TextStyle,
+    pub align: Align,
+    pub valign: VAlign,
+    /// Canvas point, from the top-left corner, the text is anchored to;
+    /// the text is centered on the canvas when unset.
+    pub position: Option<(f32, f32)>,
+    pub decorations: Decorations,
+    /// Extra space added between grapheme clusters.
+    pub letter_spacing: Length,
+    /// Extra space added to every space character, on top of letter spacing.
+    pub word_spacing: Length,
+    /// Writing direction, guessed from the text when unset.
+    pub direction: Option<Direction>,
+    pub stroke: Option<Stroke>,
+    /// Shadow and glow cast by the glyphs.
+    pub shadow: Option<Shadow>,
+    pub glow: Option<Shadow>,
+    /// Shadow and glow cast by the `bg_color` box.
+    pub bg_shadow: Option<Shadow>,
+    pub bg_glow: Option<Shadow>,
+    pub bg_box: BgBox,
+    /// How the canvas turns from the previous word's into this one's,
+    /// instantly when unset.
+    pub canvas_transition: Option<Transition>,
+    /// How the word appears and disappears, at once when unset.
+    pub transition_in: Option<WordTransition>,
+    pub transition_out: Option<WordTransition>,
+    /// How each glyph is animated, in the reading order.
+    pub glyph_animation: Option<GlyphAnimation>,
+    /// How long the word is shown, `--frequency` deciding when unset.
+    pub duration: Option<Duration>,
+    /// Animation of the word, timed from when it appears.
+    pub keyframes: Keyframes,
+}
+
+/// Shape of the box drawn behind the text when it has a `bg_color`.
+#[derive(Debug, Clone, Copy)]
+pub struct BgBox {
+    pub padding: Padding,
+    /// Corner radius in pixels.
+    pub radius: f32,
+    pub fit: BoxFit,
+    /// Width in pixels of the border, drawn around the padding.
+    pub border_width: f32,
+    /// Color of the border, the foreground color when unset.
+    pub border_color: Option<RgbaColor>,
+}
+
+/// What the box wraps before padding is added.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BoxFit {
+    /// The inked area of the glyphs.
+    Ink,
+    /// The font's ascender and descender on every line, so that the box
+    /// keeps its height whatever the text.
+    Font,
+}
+
+/// Space in pixels between the text and the edges of its box, written in
+/// the input as one number or as 2 to 4 numbers in CSS order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Padding {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Padding {
+    pub const fn uniform(padding: f32) -> Self {
+        Self { top: padding, right: padding, bottom: padding, left: padding }
+    }
+}
+
+impl<'de> Deserialize<'de> for Padding {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Padding, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Uniform(f32),
+            Sides(Vec<f32>),
+        }
+        let padding = match Raw::deserialize(deserializer)? {
+            Raw::Uniform(padding) => Some(Padding::uniform(padding)),
+            Raw::Sides(sides) => match sides[..] {
+                [all] => Some(Padding::uniform(all)),
+                [y, x] => Some(Padding { top: y, right: x, bottom: y, left: x }),
+                [top, x, bottom] => Some(Padding { top, right: x, bottom, left: x }),
+                [top, right, bottom, left] => Some(Padding { top, right, bottom, left }),
+                _ => None,
+            },
+        };
+        padding.ok_or_else(|| serde::de::Error::custom("invalid padding, expected 1 to 4 sides"))
+    }
+}
+
+/// A blurred copy of a shape drawn beneath it; a glow is a shadow
+/// without offset. Lengths are in pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct Shadow {
+    pub offset: (f32, f32),
+    /// Blur radius, twice the standard deviation of the Gaussian.
+    pub blur: f32,
+    /// How far the shape is grown before blurring.
+    pub spread: f32,
+    pub color: RgbaColor,
+}
+
+impl Shadow {
+    fn from_json(json: &ShadowJson, palette: &Palette, current_color: RgbaColor) -> Result<Self> {
+        Ok(Self {
+            offset: json.offset.unwrap_or((0., 0.)),
+            blur: json.blur.unwrap_or(0.),
+            spread: json.spread.unwrap_or(0.),
+            color: palette.color_value(&json.color)?.resolve(current_color),
+        })
+    }
+}
+
+impl GlyphAnimation {
+    fn from_json(
+        json: &GlyphAnimationJson,
+        palette: &Palette,
+        current_color: RgbaColor,
+    ) -> Result<Self> {
+        let color = match &json.color {
+            Some(color) => palette.color_value(color)?.resolve(current_color),
+            None if json.effect == GlyphEffect::Color => {
+                return Err(anyhow!("missing color, the color effect starts from it"))
+            }
+            None => current_color,
+        };
+        Ok(Self {
+            effect: json.effect,
+            timing: Transition {
+                duration_ms: json.duration_ms.unwrap_or(animation::DEFAULT_GLYPH_DURATION_MS),
+                easing: json.easing,
+            },
+            stagger: Duration::from_millis(
+                json.stagger_ms.unwrap_or(animation::DEFAULT_STAGGER_MS),
+            ),
+            amplitude: json.amplitude.unwrap_or(animation::DEFAULT_AMPLITUDE),
+            color,
+        })
+    }
+}
+
+/// Keyframes of the word, or of the scene without a `current_color`.
+fn keyframes(
+    json: &[KeyframeJson],
+    palette: &Palette,
+    current_color: Option<RgbaColor>,
+) -> Result<Keyframes> {
+    let color = |color: &Option<String>| -> Result<Option<RgbaColor>> {
+        Ok(match (color, current_color) {
+            (Some(color), Some(current_color)) => {
+                Some(palette.color_value(color)?.resolve(current_color))
+            }
+            (Some(color), None) => Some(palette.color(color)?),
+            (None, _) => None,
+        })
+    };
+    let keyframes = json
+        .iter()
+        .map(|k| {
+            Ok(Keyframe {
+                at: Duration::from_millis(k.at_ms),
+                easing: k.easing,
+                translate: k.translate,
+                scale: k.scale,
+                rotate: k.rotate,
+                opacity: k.opacity,
+                fg_color: color(&k.fg_color).context("fg_color")?,
+                canvas_color: color(&k.canvas_color).context("canvas_color")?,
+            })
+        })
+        .collect::<Result<_>>()
+        .context("keyframes")?;
+    Ok(Keyframes::new(keyframes))
+}
+
+/// Outline drawn around the glyphs, under the fill.
+#[derive(Debug, Clone, Copy)]
+pub struct Stroke {
+    pub color: RgbaColor,
+    /// How far the outline extends outside the glyphs.
+    pub width: Length,
+}
+
+/// A length in pixels or relative to the font size, written in the input
+/// as a number of pixels or as a string such as `"2px"` or `"0.1em"`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Px(f32),
+    Em(f32),
+}
+
+impl Length {
+    pub fn to_pixels(self, em: f32) -> f32 {
+        match self {
+            Length::Px(px) => px,
+            Length::Em(ems) => ems * em,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Length {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Length, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(f32),
+            String(String),
+        }
+        let parse = |s: &str| s.trim().parse::<f32>().ok();
+        let length = match Raw::deserialize(deserializer)? {
+            Raw::Number(px) => Some(Length::Px(px)),
+            Raw::String(s) => match s.trim() {
+                s if s.ends_with("em") => parse(&s[..s.len() - 2]).map(Length::Em),
+                s if s.ends_with("px") => parse(&s[..s.len() - 2]).map(Length::Px),
+                s => parse(s).map(Length::Px),
+            },
+        };
+        length.ok_or_else(|| serde::de::Error::custom("invalid length, expected px or em"))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Decorations {
+    pub underline: Option<DecorationStyle>,
+    pub strikethrough: Option<DecorationStyle>,
+    pub overline: Option<DecorationStyle>,
+    /// Color of the lines, the foreground color when unset.
+    pub color: Option<RgbaColor>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DecorationStyle {
+    Single,
+    Double,
+    Dotted,
+    Dashed,
+    Wavy,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Ltr,
+    Rtl,
+    /// Vertical text, with columns running from right to left.
+    Ttb,
+}
+
+impl Direction {
+    pub fn to_harfbuzz(self) -> harfbuzz::hb_direction_t {
+        match self {
+            Direction::Ltr => harfbuzz::HB_DIRECTION_LTR,
+            Direction::Rtl => harfbuzz::HB_DIRECTION_RTL,
+            Direction::Ttb => harfbuzz::HB_DIRECTION_TTB,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VAlign {
+    Top,
+    Middle,
+    Baseline,
+    Bottom,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TextStyle {
-    pub fg_color: RgbColor,
-    pub bg_color: Option<RgbColor>,
+    pub fg_color: Paint,
+    pub bg_color: Option<Paint>,
     pub font_attributes: FontAttributes,
 }
 
@@ -55,33 +449,166 @@ pub struct FontAttributes {
 impl Input {
     pub fn new(path: &str) -> Result<Self> {
         let input_json = InputJson::parse(path)?;
+        let palette = Palette::new(&input_json.palette)?;
         let mut words: Vec<Word> = Vec::new();
-        for word_json in input_json.words.iter() {
-            let bg_color =
-                word_json.bg_color.as_ref().map(|c| RgbColor::from_named_or_rgb_string(c).unwrap());
-            let mut buf = harfbuzz::Buffer::new()?;
-            buf.add_str(&word_json.text);
-            buf.guess_segment_properties();
-            let hb_script = buf.get_script();
-            words.push(Word {
-                text: String::from(&word_json.text),
-                canvas_color: RgbColor::from_named_or_rgb_string(&word_json.canvas_color).unwrap(),
-                style: TextStyle {
-                    fg_color: RgbColor::from_named_or_rgb_string(&word_json.fg_color).unwrap(),
-                    bg_color,
-                    font_attributes: FontAttributes {
-                        family: language::get_font(&hb_script).into(),
-                        bold: word_json.bold.unwrap_or(false),
-                        italic: word_json.italic.unwrap_or(false),
-                    },
-                },
-            });
+        for (index, word_json) in input_json.words.iter().enumerate() {
+            let generated = input_json.color_scheme.as_ref().map(|scheme| scheme.generate(index));
+            let word = word_json
+                .to_word(&input_json, &palette, generated)
+                .with_context(|| format!("word {:?}", word_json.text))?;
+            words.push(word);
         }
 
-        Ok(Self { config: Config { font_size: input_json.font_size as f64, dpi: 96 }, words })
+        Ok(Self {
+            config: Config {
+                font_size: input_json.font_size as f64,
+                dpi: 96,
+                max_width: input_json.max_width,
+            },
+            words,
+            keyframes: keyframes(&input_json.keyframes, &palette, None)?,
+        })
     }
 }
 
+impl WordJson {
+    /// Resolves the word's colors against the palette, taking what it
+    /// doesn't set from its style, then from the generated colors.
+    fn to_word(
+        &self,
+        input: &InputJson,
+        palette: &Palette,
+        generated: Option<GeneratedColors>,
+    ) -> Result<Word> {
+        let style = match &self.style {
+            Some(name) => {
+                Some(input.styles.get(name).ok_or_else(|| anyhow!("undefined style {:?}", name))?)
+            }
+            None => None,
+        };
+        let duration_ms = self.duration_ms.or(input.duration_ms);
+        if duration_ms == Some(0) {
+            return Err(anyhow!("duration_ms must be positive"));
+        }
+        let mut buf = harfbuzz::Buffer::new()?;
+        // Emoji are shaped with their own font, they shouldn't take
+        // part in picking the font for the rest of the word.
+        let mut has_text = false;
+        for run in segment::split_runs(&self.text) {
+            if run.presentation == Presentation::Text {
+                buf.add_str(&self.text[run.range]);
+                has_text = true;
+            }
+        }
+        // Without text there is no script to guess, only emoji: the
+        // Latin font still gives the word its metrics.
+        let script = if has_text {
+            buf.guess_segment_properties();
+            buf.get_script()
+        } else {
+            harfbuzz::HB_SCRIPT_LATIN
+        };
+        let family = language::get_font(&script).to_string();
+
+        let paint = |own: &Option<PaintJson>, styled: Option<&Option<PaintJson>>| {
+            own.as_ref().or_else(|| styled.and_then(Option::as_ref)).map(|p| p.resolve(palette))
+        };
+        let canvas_color = match paint(&self.canvas_color, style.map(|s| &s.canvas_color)) {
+            Some(canvas_color) => canvas_color.context("canvas_color")?,
+            None => Paint::Solid(
+                generated.as_ref().map(|g| g.canvas).ok_or_else(|| missing("canvas_color"))?,
+            ),
+        };
+        let bg_color = match paint(&self.bg_color, style.map(|s| &s.bg_color)) {
+            Some(bg_color) => Some(bg_color.context("bg_color")?),
+            None => generated.as_ref().and_then(|g| g.bg).map(Paint::Solid),
+        };
+        let fg_color = match paint(&self.fg_color, style.map(|s| &s.fg_color)) {
+            Some(fg_color) => fg_color.context("fg_color")?,
+            None => {
+                let generated = generated.as_ref().ok_or_else(|| missing("fg_color"))?;
+                let background = bg_color.as_ref().unwrap_or(&canvas_color);
+                Paint::Solid(generated.fg_on(&background.colors()))
+            }
+        };
+        // What `currentColor` stands for.
+        let current_color = fg_color.base_color();
+        let color_value = |color: &Option<String>, field: &str| -> Result<Option<ColorValue>> {
+            color
+                .as_ref()
+                .map(|c| palette.color_value(c))
+                .transpose()
+                .with_context(|| field.to_string())
+        };
+        let shadow = |shadow: &Option<ShadowJson>, field: &str| -> Result<Option<Shadow>> {
+            shadow
+                .as_ref()
+                .map(|s| Shadow::from_json(s, palette, current_color))
+                .transpose()
+                .with_context(|| field.to_string())
+        };
+
+        Ok(Word {
+            text: String::from(&self.text),
+            canvas_color,
+            style: TextStyle {
+                fg_color,
+                bg_color,
+                font_attributes: FontAttributes {
+                    family,
+                    bold: self.bold.or_else(|| style.and_then(|s| s.bold)).unwrap_or(false),
+                    italic: self.italic.or_else(|| style.and_then(|s| s.italic)).unwrap_or(false),
+                },
+            },
+            align: self.align.unwrap_or(Align::Center),
+            valign: self.valign.unwrap_or(VAlign::Middle),
+            position: self.position,
+            decorations: Decorations {
+                underline: self.underline,
+                strikethrough: self.strikethrough,
+                overline: self.overline,
+                color: color_value(&self.decoration_color, "decoration_color")?
+                    .and_then(ColorValue::specified),
+            },
+            letter_spacing: self.letter_spacing.unwrap_or(Length::Px(0.)),
+            word_spacing: self.word_spacing.unwrap_or(Length::Px(0.)),
+            direction: self.direction,
+            stroke: color_value(&self.stroke_color, "stroke_color")?.map(|c| Stroke {
+                color: c.resolve(current_color),
+                width: self.stroke_width.unwrap_or(Length::Px(1.)),
+            }),
+            shadow: shadow(&self.shadow, "shadow")?,
+            glow: shadow(&self.glow, "glow")?,
+            bg_shadow: shadow(&self.bg_shadow, "bg_shadow")?,
+            bg_glow: shadow(&self.bg_glow, "bg_glow")?,
+            bg_box: BgBox {
+                padding: self.bg_padding.unwrap_or(DEFAULT_BG_PADDING),
+                radius: self.bg_radius.unwrap_or(0.),
+                fit: self.bg_fit.unwrap_or(BoxFit::Ink),
+                border_width: self.border_width.unwrap_or(0.),
+                border_color: color_value(&self.border_color, "border_color")?
+                    .and_then(ColorValue::specified),
+            },
+            canvas_transition: self.canvas_transition.or(input.canvas_transition),
+            transition_in: self.transition_in.or(input.transition_in),
+            transition_out: self.transition_out.or(input.transition_out),
+            glyph_animation: self
+                .glyph_animation
+                .as_ref()
+                .or(input.glyph_animation.as_ref())
+                .map(|a| GlyphAnimation::from_json(a, palette, current_color))
+                .transpose()
+                .context("glyph_animation")?,
+            duration: duration_ms.map(Duration::from_millis),
+            keyframes: keyframes(&self.keyframes, palette, Some(current_color))?,
+        })
+    }
+}
+
+fn missing(field: &str) -> anyhow::Error {
+    anyhow!("missing {}, on the word, its style or from a color_scheme", field)
+}
+
 impl InputJson {
     fn parse(path: &str) -> Result<Self> {
         let data = std::fs::read_to_string(path)?;
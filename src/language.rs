@@ -1,5 +1,7 @@
 use crate::font::hbwrap as harfbuzz;
 
+pub const EMOJI_FONT: &str = "Noto Color Emoji";
+
 macro_rules! languages {
     ($( { $hb_script:ident, $font:literal } ),* ,) => {
         pub fn get_font(hb_script: &u32 ) -> &str {
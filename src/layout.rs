@@ -0,0 +1,394 @@
+use crate::font::hbwrap as harfbuzz;
+use crate::font::shaper::ShapeProperties;
+use crate::font::{FontConfiguration, GlyphInfo, GlyphRun, LoadedFont};
+use crate::input::{Align, Direction, VAlign, Word};
+use crate::utils::PixelLength;
+use anyhow::Result;
+use std::ops::Range;
+use std::rc::Rc;
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+const TATWEEL: char = '\u{0640}';
+
+pub struct PositionedGlyph {
+    pub font: Rc<LoadedFont>,
+    pub info: GlyphInfo,
+    /// Pen position of the glyph, before HarfBuzz offsets and bearings.
+    pub x: f32,
+    pub y: f32,
+    /// Whether the glyph is turned 90° clockwise around its pen position,
+    /// for horizontal runs in vertical text.
+    pub rotated: bool,
+}
+
+/// Extent and baseline of a laid out line, along the line (`left` and
+/// `right`) and across lines (`baseline`). They are screen coordinates
+/// in horizontal text; see [`Layout::to_screen`] for vertical text.
+pub struct LinePosition {
+    pub left: f32,
+    pub right: f32,
+    pub baseline: f32,
+}
+
+pub struct Layout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub lines: Vec<LinePosition>,
+    pub vertical: bool,
+}
+
+impl Layout {
+    /// Maps a point given along and across lines to the screen.
+    pub fn to_screen(&self, inline: f32, block: f32) -> (f32, f32) {
+        to_screen(self.vertical, inline, block)
+    }
+
+    /// Rank of the cluster of each glyph in the reading order of the
+    /// text, the glyphs of a cluster sharing theirs.
+    pub fn cluster_ranks(&self) -> Vec<usize> {
+        let mut clusters: Vec<u32> = self.glyphs.iter().map(|g| g.info.cluster).collect();
+        clusters.sort_unstable();
+        clusters.dedup();
+        self.glyphs
+            .iter()
+            .map(|g| clusters.binary_search(&g.info.cluster).unwrap_or_default())
+            .collect()
+    }
+}
+
+/// Vertical lines run downwards and stack leftwards.
+fn to_screen(vertical: bool, inline: f32, block: f32) -> (f32, f32) {
+    if vertical {
+        (-block, inline)
+    } else {
+        (inline, block)
+    }
+}
+
+struct Line {
+    range: Range<usize>,
+    runs: Vec<GlyphRun>,
+    width: f32,
+    /// Whether the line is stretched when justifying; false for the last
+    /// line of a paragraph.
+    justify: bool,
+}
+
+/// Letter and word spacing of a word, resolved to pixels.
+#[derive(Copy, Clone)]
+struct Spacing {
+    letter: f32,
+    word: f32,
+}
+
+/// Breaks the text of `word` into lines no longer than `max_size` allows
+/// and positions its glyphs according to the word's alignment and anchor.
+/// Coordinates are relative to the center of a canvas of `canvas_size`.
+///
+/// Layout happens along and across lines; in vertical text, `align`
+/// applies along columns and `valign` across them, top being the right
+/// edge of the first column.
+pub fn layout_word(
+    fontconfig: &FontConfiguration,
+    word: &Word,
+    max_size: (f32, f32),
+    canvas_size: (f32, f32),
+) -> Result<Layout> {
+    let vertical = word.direction == Some(Direction::Ttb);
+    let em = fontconfig.pixel_size() as f32;
+    let spacing = Spacing {
+        letter: word.letter_spacing.to_pixels(em),
+        word: word.word_spacing.to_pixels(em),
+    };
+    let max_width = if vertical { max_size.1 } else { max_size.0 };
+    let mut lines = break_lines(fontconfig, word, spacing, max_width)?;
+    let metrics = fontconfig.get_font(&word.style.font_attributes)?.metrics();
+    let line_height = metrics.line_height.get() as f32;
+    let ascender = metrics.ascender.get() as f32;
+    let descender = metrics.descender.get() as f32;
+
+    // Columns are centered on their position; rotated runs sit on a
+    // baseline shifted so that they are centered too.
+    let (line_top, line_bottom) =
+        if vertical { (-line_height / 2., line_height / 2.) } else { (-ascender, -descender) };
+    let baseline_shift = if vertical { (ascender + descender) / 2. } else { 0. };
+
+    let block_width = lines.iter().fold(0f32, |acc, line| acc.max(line.width));
+    let last_baseline = (lines.len().max(1) - 1) as f32 * line_height;
+    let (block_top, block_bottom) = (line_top, last_baseline + line_bottom);
+
+    // Offset of the block's reference point from its left edge and from
+    // the first baseline.
+    let reference_x = match word.align {
+        Align::Left | Align::Justify => 0.,
+        Align::Center => block_width / 2.,
+        Align::Right => block_width,
+    };
+    let reference_y = match word.valign {
+        VAlign::Top => block_top,
+        VAlign::Middle => (block_top + block_bottom) / 2.,
+        VAlign::Baseline => 0.,
+        VAlign::Bottom => block_bottom,
+    };
+    // Without an explicit position the block is centered on the canvas
+    // along lines, and aligned on its middle by default across them.
+    let (left, anchor_y) = match word.position {
+        Some((x, y)) if vertical => (y - canvas_size.1 / 2. - reference_x, canvas_size.0 / 2. - x),
+        Some((x, y)) => (x - canvas_size.0 / 2. - reference_x, y - canvas_size.1 / 2.),
+        None => (-block_width / 2., 0.),
+    };
+
+    if word.align == Align::Justify {
+        for line in lines.iter_mut().filter(|line| line.justify) {
+            let text = &word.text[line.range.clone()];
+            if let Some(runs) =
+                justify_with_kashida(fontconfig, text, word, spacing, &line.runs, block_width)?
+            {
+                line.width = width_of(&runs);
+                line.runs = runs;
+            }
+        }
+    }
+
+    let mut glyphs = Vec::new();
+    let mut positions = Vec::new();
+    let mut y = anchor_y - reference_y;
+    for line in lines {
+        let mut x = left
+            + match word.align {
+                Align::Left | Align::Justify => 0.,
+                Align::Center => (block_width - line.width) / 2.,
+                Align::Right => block_width - line.width,
+            };
+        let extra_space = if word.align == Align::Justify && line.justify {
+            let spaces = line
+                .runs
+                .iter()
+                .flat_map(|run| run.glyphs.iter())
+                .filter(|info| is_space(&word.text[line.range.clone()], info))
+                .count();
+            if spaces > 0 {
+                (block_width - line.width) / spaces as f32
+            } else {
+                0.
+            }
+        } else {
+            0.
+        };
+        let line_left = x;
+        for run in line.runs {
+            let rotated = vertical && !run.is_vertical();
+            let baseline = if rotated { y + baseline_shift } else { y };
+            for info in &run.glyphs {
+                let mut info = info.clone();
+                let space = is_space(&word.text[line.range.clone()], &info);
+                info.cluster += line.range.start as u32;
+                let (pen_x, pen_y) = to_screen(vertical, x, baseline);
+                x += run.advance(&info).get() as f32;
+                glyphs.push(PositionedGlyph {
+                    font: Rc::clone(&run.font),
+                    info,
+                    x: pen_x,
+                    y: pen_y,
+                    rotated,
+                });
+                if space {
+                    x += extra_space;
+                }
+            }
+        }
+        positions.push(LinePosition { left: line_left, right: x, baseline: y + baseline_shift });
+        y += line_height;
+    }
+    Ok(Layout { glyphs, lines: positions, vertical })
+}
+
+/// Whether the glyph, whose cluster is relative to `text`, renders a
+/// space character.
+fn is_space(text: &str, info: &GlyphInfo) -> bool {
+    text[info.cluster as usize..].starts_with(char::is_whitespace)
+}
+
+/// Greedy line breaking on UAX #14 break opportunities: a line is
+/// extended one opportunity at a time until it no longer fits. The text
+/// is shaped once and candidate lines measured from the advances of
+/// their clusters; only the chosen lines are shaped on their own.
+fn break_lines(
+    fontconfig: &FontConfiguration,
+    word: &Word,
+    spacing: Spacing,
+    max_width: f32,
+) -> Result<Vec<Line>> {
+    let text = &word.text;
+    let advances: Vec<(usize, f32)> = shape_line(fontconfig, text, word, spacing)?
+        .iter()
+        .flat_map(|run| {
+            run.glyphs
+                .iter()
+                .map(move |info| (info.cluster as usize, run.advance(info).get() as f32))
+        })
+        .collect();
+    let width_between = |range: Range<usize>| -> f32 {
+        advances.iter().filter(|(cluster, _)| range.contains(cluster)).map(|(_, a)| a).sum()
+    };
+    let mut ranges = Vec::new();
+    let mut line_start = 0;
+    let mut last_fit = None;
+    for (pos, opportunity) in linebreaks(text) {
+        if let Some(fit) = last_fit {
+            let candidate = text[line_start..pos].trim_end();
+            if width_between(line_start..line_start + candidate.len()) > max_width {
+                ranges.push((line_start..fit, true));
+                line_start = fit;
+            }
+        }
+        if opportunity == BreakOpportunity::Mandatory {
+            ranges.push((line_start..pos, false));
+            line_start = pos;
+            last_fit = None;
+        } else {
+            last_fit = Some(pos);
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (range, justify) in ranges {
+        // Trailing spaces and the newline itself hang past the line end.
+        let range = range.start..range.start + text[range].trim_end().len();
+        let runs = shape_line(fontconfig, &text[range.clone()], word, spacing)?;
+        let width = width_of(&runs);
+        lines.push(Line { range, runs, width, justify });
+    }
+    Ok(lines)
+}
+
+/// Shapes a line of `word` and applies letter and word spacing to it.
+fn shape_line(
+    fontconfig: &FontConfiguration,
+    text: &str,
+    word: &Word,
+    spacing: Spacing,
+) -> Result<Vec<GlyphRun>> {
+    let mut runs = fontconfig.shape(text, &word.style.font_attributes, word.direction)?;
+    apply_spacing(&mut runs, text, spacing);
+    Ok(runs)
+}
+
+/// Adds spacing after the last glyph of every cluster but the last one,
+/// so ligatures and mark stacks stay intact. Letter spacing is skipped
+/// inside cursive scripts, where it would break the joining.
+fn apply_spacing(runs: &mut [GlyphRun], text: &str, spacing: Spacing) {
+    if spacing.letter == 0. && spacing.word == 0. {
+        return;
+    }
+    let positions: Vec<(usize, usize)> = runs
+        .iter()
+        .enumerate()
+        .flat_map(|(r, run)| (0..run.glyphs.len()).map(move |g| (r, g)))
+        .collect();
+    for (i, &(r, g)) in positions.iter().enumerate() {
+        let next = match positions.get(i + 1) {
+            Some(&(next_r, next_g)) => &runs[next_r].glyphs[next_g],
+            None => break,
+        };
+        let glyph = &runs[r].glyphs[g];
+        if next.cluster == glyph.cluster {
+            continue;
+        }
+        let cursive = is_cursive(runs[r].properties.script) && positions[i + 1].0 == r;
+        let mut extra = if cursive { 0. } else { spacing.letter };
+        if is_space(text, glyph) {
+            extra += spacing.word;
+        }
+        runs[r].add_advance(g, PixelLength::new(extra as f64));
+    }
+}
+
+fn is_cursive(script: harfbuzz::hb_script_t) -> bool {
+    matches!(
+        script,
+        harfbuzz::HB_SCRIPT_ARABIC
+            | harfbuzz::HB_SCRIPT_SYRIAC
+            | harfbuzz::HB_SCRIPT_NKO
+            | harfbuzz::HB_SCRIPT_MONGOLIAN
+            | harfbuzz::HB_SCRIPT_MANDAIC
+    )
+}
+
+/// Stretches an Arabic line towards `target_width` by elongating joins
+/// with tatweels, spread evenly over the line. Returns `None` when the
+/// line isn't Arabic or the font has no tatweel; what kashidas can't fill
+/// is left to the space stretching.
+fn justify_with_kashida(
+    fontconfig: &FontConfiguration,
+    text: &str,
+    word: &Word,
+    spacing: Spacing,
+    runs: &[GlyphRun],
+    target_width: f32,
+) -> Result<Option<Vec<GlyphRun>>> {
+    if ShapeProperties::guess(text)?.script != harfbuzz::HB_SCRIPT_ARABIC {
+        return Ok(None);
+    }
+    let tatweel = shape_line(fontconfig, &TATWEEL.to_string(), word, spacing)?;
+    let tatweel_width = match tatweel.first().and_then(|run| Some((run, run.glyphs.first()?))) {
+        Some((run, glyph)) if glyph.glyph_pos != 0 => run.advance(glyph).get() as f32,
+        _ => return Ok(None),
+    };
+    let candidates = kashida_points(text);
+    let count = ((target_width - width_of(runs)) / tatweel_width).floor();
+    if candidates.is_empty() || tatweel_width <= 0. || count < 1. {
+        return Ok(None);
+    }
+    let count = count as usize;
+
+    // Byte offsets of the stretched text map back to the letter each
+    // tatweel elongates, so clusters keep pointing into `text`.
+    let mut stretched = String::new();
+    let mut origin = Vec::new();
+    for (i, (offset, c)) in text.char_indices().enumerate() {
+        stretched.push(c);
+        origin.extend(std::iter::repeat(offset).take(c.len_utf8()));
+        if let Some(n) = candidates.iter().position(|&p| p == i) {
+            let share = count / candidates.len() + usize::from(n < count % candidates.len());
+            for _ in 0..share {
+                stretched.push(TATWEEL);
+                origin.extend(std::iter::repeat(offset).take(TATWEEL.len_utf8()));
+            }
+        }
+    }
+
+    let clusters: Vec<(u32, u32)> =
+        runs.iter().flat_map(|run| run.glyphs.iter().map(|g| (g.cluster, g.cluster_len))).collect();
+    let mut stretched_runs = shape_line(fontconfig, &stretched, word, spacing)?;
+    for glyph in stretched_runs.iter_mut().flat_map(|run| run.glyphs.iter_mut()) {
+        let offset = origin[glyph.cluster as usize] as u32;
+        if let Some(&(cluster, len)) =
+            clusters.iter().find(|(start, len)| (*start..start + len).contains(&offset))
+        {
+            glyph.cluster = cluster;
+            glyph.cluster_len = len;
+        }
+    }
+    Ok(Some(stretched_runs))
+}
+
+/// Indices of the characters after which a tatweel may be inserted: an
+/// Arabic letter that joins to the following letter.
+fn kashida_points(text: &str) -> Vec<usize> {
+    let is_letter = |c: char| ('\u{0620}'..='\u{064A}').contains(&c) && c != TATWEEL;
+    // Letters that only join on their right side and never to the next.
+    let right_joining = |c: char| "آأؤإاةدذرزوى".contains(c);
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| is_letter(pair[0]) && !right_joining(pair[0]) && is_letter(pair[1]))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn width_of(runs: &[GlyphRun]) -> f32 {
+    runs.iter()
+        .flat_map(|run| run.glyphs.iter().map(move |info| run.advance(info).get() as f32))
+        .sum()
+}
@@ -1,8 +1,11 @@
 #[macro_use]
 extern crate glium;
 
+use animation::{GlyphAnimation, GlyphEffect, Transform};
+use anyhow::bail;
 use anyhow::Result;
-use clap::{crate_description, crate_name, crate_version, AppSettings, Arg};
+use clap::{crate_description, crate_name, crate_version, AppSettings, Arg, ArgMatches};
+use contrast::{Contrast, Thresholds};
 use font::FontConfiguration;
 use glium::glutin::dpi::LogicalSize;
 use glium::glutin::event::Event;
@@ -12,45 +15,71 @@ use glium::glutin::event_loop::ControlFlow;
 use glium::glutin::event_loop::EventLoop;
 use glium::glutin::window::WindowBuilder;
 use glium::glutin::ContextBuilder;
+use glium::uniforms::{
+    MagnifySamplerFilter, SamplerBehavior, SamplerWrapFunction, UniformValue, Uniforms,
+};
 use glium::{BlendingFunction, Display, Frame, LinearBlendingFactor, Surface};
 use input::{Input, Word};
-use render_state::RenderState;
+use render_state::{PaintUniforms, RenderState};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::time::{Duration, Instant};
+use timeline::Timeline;
 
+mod animation;
 mod bitmaps;
 mod color;
+mod contrast;
+mod decoration;
 mod font;
 mod glyph_atlas;
 mod input;
 mod language;
+mod layout;
 mod render_state;
+mod shadow;
+mod timeline;
 mod utils;
 
 const FPS: u32 = 60;
+const MARGIN: f64 = 30.;
 static DEFAULT_INPUT_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/0.json");
 
-fn run(input_path: &str, frequency: u32) -> Result<()> {
+fn run(input_path: &str, frequency: u32, thresholds: Thresholds, cache_stats: bool) -> Result<()> {
+    let input = Rc::new(Input::new(input_path)?);
+    for (word, contrast) in low_contrast_words(&input, &thresholds) {
+        eprintln!("warning: low contrast for {}", describe(word, contrast));
+    }
     let event_loop = EventLoop::new();
     let (window_width, window_height) = (720., 405.);
     let wb = WindowBuilder::new().with_inner_size(LogicalSize::new(window_width, window_height));
     let cb = ContextBuilder::new();
     let display = Display::new(wb, cb, &event_loop)?;
-    let input = Rc::new(Input::new(input_path)?);
     let fontconfig = Rc::new(FontConfiguration::new(input.config.font_size, input.config.dpi)?);
-    let render_state = RefCell::new(RenderState::new(&display)?);
-    let mut frame_count = 0;
-    let mut count = 0;
+    let max_width = input.config.max_width.unwrap_or(window_width - 2. * MARGIN);
+    let max_height = window_height - 2. * MARGIN;
+    let render_state = RefCell::new(RenderState::new(
+        &display,
+        (window_width as f32, window_height as f32),
+        (max_width as f32, max_height as f32),
+    )?);
+    let default_duration = Duration::from_secs_f64(1. / frequency as f64);
+    let mut timeline = Timeline::new(
+        input.words.iter().map(|w| w.duration.unwrap_or(default_duration)).collect(),
+        input.keyframes.clone(),
+        input.words.iter().map(|w| w.keyframes.clone()).collect(),
+    );
     event_loop.run(move |event, _, control_flow| {
         match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => {
-                    *control_flow = ControlFlow::Exit;
-                    return;
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                if cache_stats {
+                    let stats = fontconfig.shape_cache_stats();
+                    eprintln!("shape cache: {} hits, {} misses", stats.hits, stats.misses);
                 }
-                _ => return,
-            },
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            Event::WindowEvent { .. } => return,
             Event::NewEvents(cause) => match cause {
                 StartCause::ResumeTimeReached { .. } => (),
                 StartCause::Init => (),
@@ -70,17 +99,14 @@ fn run(input_path: &str, frequency: u32) -> Result<()> {
             &input.words,
             window_width,
             window_height,
-            &mut count,
-            frame_count,
-            frequency,
+            &mut timeline,
         )
         .unwrap();
         target.finish().unwrap();
-
-        frame_count += 1;
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 fn paint_screen(
     fontconfig: &Rc<FontConfiguration>,
     render_state: &RefCell<RenderState>,
@@ -89,9 +115,7 @@ fn paint_screen(
     words: &[Word],
     window_width: f64,
     window_height: f64,
-    count: &mut u32,
-    frame_count: u32,
-    frequency: u32,
+    timeline: &mut Timeline,
 ) -> Result<()> {
     let mut gl_state = render_state.borrow_mut();
     let projection = euclid::Transform3D::<f32, f32, f32>::ortho(
@@ -120,57 +144,351 @@ fn paint_screen(
         ..Default::default()
     };
 
-    if frame_count % (60 / frequency) == 0 {
-        let idx = *count as usize % words.len();
-        let w = &words[idx];
-        gl_state.word = Some(w.clone());
+    let now = Instant::now();
+    let tick = timeline.tick(now);
+    if tick.changed {
+        gl_state.word = Some(words[tick.index].clone());
         gl_state.compute_glyph_vertices(display, fontconfig)?;
-        *count += 1;
     }
 
     gl_state.compute_bg_vertices(display, window_width, window_height)?;
 
+    let bounds = gl_state.word_bounds;
+    let canvas_layer = Layer {
+        projection,
+        transform: Transform::default(),
+        origin: (0., 0.),
+        glyph_animation: None,
+    };
+    let shown_for = now.saturating_duration_since(tick.word_start).as_secs_f32();
+    let word_layer = Layer {
+        projection,
+        transform: gl_state
+            .transform(tick.word_start, tick.word_duration, now)
+            .combine(tick.transform),
+        origin: ((bounds.left + bounds.right) / 2., (bounds.top + bounds.bottom) / 2.),
+        glyph_animation: gl_state.word.as_ref().unwrap().glyph_animation.map(|a| (a, shown_for)),
+    };
+    // While blurring in or out, the text fades between its blurred copy
+    // and itself.
+    let blur = word_layer.transform.blur;
+
+    // A translucent canvas blends with black, not with the last frame.
+    frame.clear_color(0.0, 0.0, 0.0, 1.0);
     frame.draw(
         gl_state.bg_vertex_buffer.as_ref().unwrap(),
         gl_state.bg_index_buffer.as_ref().unwrap(),
         &gl_state.glyph_program,
-        &uniform! {
-            projection: projection,
-            draw_bg: true
-        },
+        &canvas_layer
+            .uniforms(
+                gl_state.canvas_paint.as_ref(),
+                1.,
+                uniform! {
+                    draw_bg: true,
+                    draw_box: false
+                },
+            )
+            .filled(tick.canvas_color),
         &draw_params_with_alpha,
     )?;
 
-    if gl_state.word.as_ref().unwrap().style.bg_color.is_some() {
+    let tex = gl_state.glyph_atlas.atlas.texture();
+
+    if let Some(bg_shadow_vertices) = gl_state.bg_shadow_vertex_buffer.as_ref() {
+        frame.draw(
+            bg_shadow_vertices,
+            gl_state.bg_shadow_index_buffer.as_ref().unwrap(),
+            &gl_state.glyph_program,
+            &word_layer.uniforms(
+                None,
+                1.,
+                uniform! {
+                    glyph_tex: &*tex,
+                    draw_bg: false,
+                    draw_box: false
+                },
+            ),
+            &draw_params_with_alpha,
+        )?;
+    }
+
+    if let Some(bg_box) = gl_state.glyph_bg_box {
+        let word = gl_state.word.as_ref().unwrap();
+        let border_color =
+            word.bg_box.border_color.unwrap_or_else(|| word.style.fg_color.base_color());
         frame.draw(
             gl_state.glyph_bg_vertex_buffer.as_ref().unwrap(),
             gl_state.glyph_bg_index_buffer.as_ref().unwrap(),
             &gl_state.glyph_program,
-            &uniform! {
-                projection: projection,
-                draw_bg: true
-            },
-            &Default::default(),
+            &word_layer.uniforms(
+                gl_state.bg_paint.as_ref(),
+                1.,
+                uniform! {
+                    draw_bg: true,
+                    draw_box: true,
+                    box_rect: [bg_box.left, bg_box.top, bg_box.right, bg_box.bottom],
+                    box_radius: word.bg_box.radius,
+                    border_width: word.bg_box.border_width,
+                    border_color: color::to_tuple_rgba(border_color)
+                },
+            ),
+            &draw_params_with_alpha,
         )?;
     }
 
-    let tex = gl_state.glyph_atlas.atlas.texture();
+    if let Some(shadow_vertices) = gl_state.shadow_vertex_buffer.as_ref() {
+        frame.draw(
+            shadow_vertices,
+            gl_state.shadow_index_buffer.as_ref().unwrap(),
+            &gl_state.glyph_program,
+            &word_layer.glyph_uniforms(
+                None,
+                1. - blur,
+                uniform! {
+                    glyph_tex: &*tex,
+                    draw_bg: false,
+                    draw_box: false
+                },
+            ),
+            &draw_params_with_alpha,
+        )?;
+    }
+
+    if let Some(stroke_vertices) = gl_state.stroke_vertex_buffer.as_ref() {
+        frame.draw(
+            stroke_vertices,
+            gl_state.stroke_index_buffer.as_ref().unwrap(),
+            &gl_state.glyph_program,
+            &word_layer.glyph_uniforms(
+                None,
+                1. - blur,
+                uniform! {
+                    glyph_tex: &*tex,
+                    draw_bg: false,
+                    draw_box: false
+                },
+            ),
+            &draw_params_with_alpha,
+        )?;
+    }
+
+    if let (Some(blur_vertices), true) = (gl_state.blur_vertex_buffer.as_ref(), blur > 0.) {
+        frame.draw(
+            blur_vertices,
+            gl_state.blur_index_buffer.as_ref().unwrap(),
+            &gl_state.glyph_program,
+            &word_layer
+                .glyph_uniforms(
+                    gl_state.fg_paint.as_ref(),
+                    blur,
+                    uniform! {
+                        glyph_tex: &*tex,
+                        draw_bg: false,
+                        draw_box: false
+                    },
+                )
+                .filled(tick.fg_color),
+            &draw_params_with_alpha,
+        )?;
+    }
 
     frame.draw(
         gl_state.glyph_vertex_buffer.as_ref().unwrap(),
         gl_state.glyph_index_buffer.as_ref().unwrap(),
         &gl_state.glyph_program,
-        &uniform! {
-            projection: projection,
-            glyph_tex: &*tex,
-            draw_bg: false
-        },
+        &word_layer
+            .glyph_uniforms(
+                gl_state.fg_paint.as_ref(),
+                1. - blur,
+                uniform! {
+                    glyph_tex: &*tex,
+                    draw_bg: false,
+                    draw_box: false
+                },
+            )
+            .filled(tick.fg_color),
         &draw_params_with_alpha,
     )?;
 
+    let decoration_indices = gl_state.decoration_index_buffer.as_ref().unwrap();
+    if decoration_indices.len() > 0 {
+        // Lines without a color of their own follow the text's paint.
+        let word = gl_state.word.as_ref().unwrap();
+        let (paint, fill) = match word.decorations.color {
+            Some(_) => (None, None),
+            None => (gl_state.fg_paint.as_ref(), tick.fg_color),
+        };
+        frame.draw(
+            gl_state.decoration_vertex_buffer.as_ref().unwrap(),
+            decoration_indices,
+            &gl_state.glyph_program,
+            &word_layer
+                .uniforms(
+                    paint,
+                    1. - blur,
+                    uniform! {
+                        draw_bg: true,
+                        draw_box: false
+                    },
+                )
+                .filled(fill),
+            &draw_params_with_alpha,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// What the draw calls of a layer share: the canvas stays put while
+/// the word is transformed by its transitions.
+#[derive(Clone, Copy)]
+struct Layer {
+    projection: [[f32; 4]; 4],
+    transform: Transform,
+    /// Point the word is scaled around.
+    origin: (f32, f32),
+    /// Per-glyph animation of the word and seconds since it appeared.
+    glyph_animation: Option<(GlyphAnimation, f32)>,
+}
+
+impl Layer {
+    /// Uniforms of a draw call filled with `paint`, solid colors coming
+    /// from the vertices when `None`, and faded by `opacity`.
+    fn uniforms<'a, U: Uniforms>(
+        &self,
+        paint: Option<&'a PaintUniforms>,
+        opacity: f32,
+        uniforms: U,
+    ) -> DrawUniforms<'a, U> {
+        DrawUniforms { layer: *self, paint, opacity, glyphs: false, fill: None, uniforms }
+    }
+
+    /// Uniforms of a draw call of glyph sprites, which take part in the
+    /// per-glyph animation. Those filled with a paint are the text
+    /// itself, which alone changes color.
+    fn glyph_uniforms<'a, U: Uniforms>(
+        &self,
+        paint: Option<&'a PaintUniforms>,
+        opacity: f32,
+        uniforms: U,
+    ) -> DrawUniforms<'a, U> {
+        DrawUniforms { glyphs: true, ..self.uniforms(paint, opacity, uniforms) }
+    }
+}
+
+/// Glium keeps uniform values from one draw call to the next, so every
+/// call sets the layer's and the paint's.
+struct DrawUniforms<'a, U> {
+    layer: Layer,
+    paint: Option<&'a PaintUniforms>,
+    opacity: f32,
+    glyphs: bool,
+    /// Solid color replacing the paint, for keyframed colors.
+    fill: Option<(f32, f32, f32, f32)>,
+    uniforms: U,
+}
+
+impl<U> DrawUniforms<'_, U> {
+    fn filled(self, fill: Option<(f32, f32, f32, f32)>) -> Self {
+        Self { fill, ..self }
+    }
+}
+
+impl<U: Uniforms> Uniforms for DrawUniforms<'_, U> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut output: F) {
+        let transform = &self.layer.transform;
+        output("projection", UniformValue::Mat4(self.layer.projection));
+        output("offset", UniformValue::Vec2([transform.offset.0, transform.offset.1]));
+        output("scale", UniformValue::Float(transform.scale));
+        output("rotation", UniformValue::Float(transform.rotation));
+        output("origin", UniformValue::Vec2([self.layer.origin.0, self.layer.origin.1]));
+        output("opacity", UniformValue::Float(transform.opacity * self.opacity));
+        let (reveal_dir, reveal_edge) = transform.reveal;
+        output("reveal_dir", UniformValue::Vec2([reveal_dir.0, reveal_dir.1]));
+        output("reveal_edge", UniformValue::Float(reveal_edge));
+        match self.paint {
+            Some(paint) => {
+                output("paint_kind", UniformValue::SignedInt(paint.kind));
+                output("paint_rect", UniformValue::Vec4(paint.rect));
+                output("paint_angle", UniformValue::Float(paint.angle));
+                let sampler = SamplerBehavior {
+                    wrap_function: (
+                        SamplerWrapFunction::Clamp,
+                        SamplerWrapFunction::Clamp,
+                        SamplerWrapFunction::Clamp,
+                    ),
+                    magnify_filter: MagnifySamplerFilter::Linear,
+                    ..Default::default()
+                };
+                output("paint_ramp", UniformValue::Texture2d(&paint.ramp, Some(sampler)));
+            }
+            None => output("paint_kind", UniformValue::SignedInt(0)),
+        }
+        match self.fill {
+            Some((r, g, b, a)) => {
+                output("use_fill", UniformValue::Bool(true));
+                output("fill", UniformValue::Vec4([r, g, b, a]));
+            }
+            None => output("use_fill", UniformValue::Bool(false)),
+        }
+        match self.layer.glyph_animation.filter(|_| self.glyphs) {
+            Some((animation, time)) => {
+                let effect = match animation.effect {
+                    GlyphEffect::Color if self.paint.is_none() => 0,
+                    effect => effect.id(),
+                };
+                output("glyph_effect", UniformValue::SignedInt(effect));
+                output("glyph_time", UniformValue::Float(time));
+                output(
+                    "glyph_duration",
+                    UniformValue::Float(animation.timing.duration().as_secs_f32()),
+                );
+                output(
+                    "glyph_easing",
+                    UniformValue::Vec4(animation.timing.easing.control_points()),
+                );
+                output("glyph_amplitude", UniformValue::Float(animation.amplitude));
+                let color = color::to_tuple_rgba(animation.color);
+                output("glyph_color", UniformValue::Vec4([color.0, color.1, color.2, color.3]));
+            }
+            None => output("glyph_effect", UniformValue::SignedInt(0)),
+        }
+        self.uniforms.visit_values(output);
+    }
+}
+
+/// Reports the words whose colors fall below the contrast thresholds.
+fn lint(input_path: &str, thresholds: Thresholds) -> Result<()> {
+    let input = Input::new(input_path)?;
+    let failing = low_contrast_words(&input, &thresholds);
+    for (word, contrast) in failing.iter() {
+        println!("{}", describe(word, *contrast));
+    }
+    if !failing.is_empty() {
+        bail!(
+            "{} of {} words below a contrast ratio of {} or an APCA Lc of {}",
+            failing.len(),
+            input.words.len(),
+            thresholds.ratio,
+            thresholds.lc
+        );
+    }
     Ok(())
 }
 
+fn low_contrast_words<'a>(input: &'a Input, thresholds: &Thresholds) -> Vec<(&'a Word, Contrast)> {
+    input
+        .words
+        .iter()
+        .map(|word| (word, Contrast::of_word(word)))
+        .filter(|(_, contrast)| !contrast.passes(thresholds))
+        .collect()
+}
+
+fn describe(word: &Word, contrast: Contrast) -> String {
+    format!("\"{}\": contrast ratio {:.2}:1, APCA Lc {:.1}", word.text, contrast.ratio, contrast.lc)
+}
+
 fn main() -> Result<()> {
     let matches = clap::Command::new(crate_name!())
         .version(crate_version!())
@@ -181,24 +499,71 @@ fn main() -> Result<()> {
                 .short('i')
                 .long("input")
                 .help("Which input to use.")
-                .takes_value(true),
+                .takes_value(true)
+                .global(true),
         )
         .arg(
             Arg::new("frequency")
                 .short('f')
                 .long("frequency")
                 .default_value("6")
-                .help("frequency in frame per second.")
+                .help("Words shown per second, for the words without a duration_ms.")
                 .takes_value(true)
                 .validator(|t| match t.parse::<u32>() {
-                    Ok(_) => Ok(()),
-                    Err(_) => Err(String::from("must be a number")),
+                    Ok(frequency) if frequency > 0 => Ok(()),
+                    _ => Err(String::from("must be a positive number")),
                 }),
         )
+        .arg(
+            Arg::new("cache-stats")
+                .long("cache-stats")
+                .help("Prints the shape cache hits and misses on exit."),
+        )
+        .arg(
+            Arg::new("min-contrast")
+                .long("min-contrast")
+                .default_value("4.5")
+                .help("WCAG 2.x contrast ratio below which words are reported.")
+                .takes_value(true)
+                .global(true)
+                .validator(validate_number),
+        )
+        .arg(
+            Arg::new("min-lc")
+                .long("min-lc")
+                .default_value("60")
+                .help("APCA lightness contrast below which words are reported.")
+                .takes_value(true)
+                .global(true)
+                .validator(validate_number),
+        )
+        .subcommand(
+            clap::Command::new("lint")
+                .about("Checks the contrast of every word against its background."),
+        )
         .get_matches();
 
+    if let Some(lint_matches) = matches.subcommand_matches("lint") {
+        let input_path = lint_matches.value_of("input").unwrap_or(DEFAULT_INPUT_FILE);
+        return lint(input_path, parse_thresholds(lint_matches)?);
+    }
     let input_path = matches.value_of("input").unwrap_or(DEFAULT_INPUT_FILE);
+    let thresholds = parse_thresholds(&matches)?;
     let frequency: u32 = matches.value_of("frequency").unwrap().parse()?;
-    run(input_path, frequency)?;
+    run(input_path, frequency, thresholds, matches.is_present("cache-stats"))?;
     Ok(())
 }
+
+fn parse_thresholds(matches: &ArgMatches) -> Result<Thresholds> {
+    Ok(Thresholds {
+        ratio: matches.value_of("min-contrast").unwrap().parse()?,
+        lc: matches.value_of("min-lc").unwrap().parse()?,
+    })
+}
+
+fn validate_number(value: &str) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(String::from("must be a number")),
+    }
+}
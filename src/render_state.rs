@@ -1,17 +1,27 @@
-use crate::color;
-use crate::font::FontConfiguration;
-use crate::glyph_atlas::GlyphAtlas;
-use crate::input::Word;
+use crate::animation::{Transform, Transition};
+use crate::bitmaps::atlas::Sprite;
+use crate::color::{self, Paint, RgbaColor};
+use crate::decoration::{self, Quad};
+use crate::font::{FontConfiguration, FontMetrics};
+use crate::glyph_atlas::{BoxKey, GlyphAtlas, GlyphKey, GlyphTexture, SpriteKind};
+use crate::input::{BoxFit, Shadow, Stroke, Word};
+use crate::layout::{self, Layout, PositionedGlyph};
+use crate::shadow;
+use crate::utils::PixelLength;
 use anyhow::Result;
-use glium::texture::SrgbTexture2d;
+use glium::texture::{RawImage2d, SrgbTexture2d};
 use glium::Display;
 use glium::Program;
+use glium::Texture2d;
 use glium::{IndexBuffer, VertexBuffer};
-
-const PADDING: f32 = 15.;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 const ATLAS_SIZE: usize = 8192;
 
+/// Texels in the ramp a gradient is baked into.
+const RAMP_SIZE: usize = 256;
+
 static GLYPH_VERTEX_SHADER: &str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/shaders/g_vertex.glsl"));
 
@@ -29,9 +39,62 @@ pub struct Vertex {
     pub tex: (f32, f32),
     pub fg_color: (f32, f32, f32, f32),
     pub bg_color: (f32, f32, f32, f32),
+    pub has_color: f32,
+    /// Seconds after the word appears that the glyph's animation starts.
+    pub delay: f32,
+}
+
+implement_vertex!(Vertex, position, tex, fg_color, bg_color, has_color, delay);
+
+/// How a draw call fills its shapes: gradients are sampled from a ramp
+/// texture across `rect`, solid colors come from the vertices.
+pub struct PaintUniforms {
+    pub paint: Paint,
+    /// 0 for solid colors, 1 for linear and 2 for radial gradients.
+    pub kind: i32,
+    pub rect: [f32; 4],
+    /// Angle of linear gradients, in radians.
+    pub angle: f32,
+    pub ramp: Texture2d,
 }
 
-implement_vertex!(Vertex, position, tex, fg_color, bg_color);
+impl PaintUniforms {
+    pub fn new(display: &Display, paint: &Paint, rect: Quad) -> Result<Self> {
+        Self::blend(display, paint, paint, 1., rect)
+    }
+
+    /// `from` turned into `to` by `amount`, from 0 to 1, colors along the
+    /// gradients being mixed in OKLab. The gradient keeps the shape of
+    /// `to`, or of `from` when `to` is solid.
+    pub fn blend(
+        display: &Display,
+        from: &Paint,
+        to: &Paint,
+        amount: f32,
+        rect: Quad,
+    ) -> Result<Self> {
+        let shape = if let Paint::Solid(_) = to { from } else { to };
+        let (kind, angle) = match shape {
+            Paint::Solid(_) => (0, 0.),
+            Paint::Linear { angle, .. } => (1, angle.to_radians()),
+            Paint::Radial { .. } => (2, 0.),
+        };
+        let mut data = Vec::with_capacity(RAMP_SIZE * 4);
+        for i in 0..RAMP_SIZE {
+            let t = i as f32 / (RAMP_SIZE - 1) as f32;
+            let (r, g, b, a) = color::mix_oklab(from.sample(t), to.sample(t), amount);
+            data.extend([r, g, b, a].iter().map(|c| (c * 255.).round() as u8));
+        }
+        let image = RawImage2d::from_raw_rgba(data, (RAMP_SIZE as u32, 1));
+        Ok(Self {
+            paint: to.clone(),
+            kind,
+            rect: [rect.left, rect.top, rect.right, rect.bottom],
+            angle,
+            ramp: Texture2d::new(display, image)?,
+        })
+    }
+}
 
 pub struct RenderState {
     pub glyph_atlas: GlyphAtlas<SrgbTexture2d>,
@@ -40,13 +103,39 @@ pub struct RenderState {
     pub glyph_index_buffer: Option<IndexBuffer<u32>>,
     pub glyph_bg_vertex_buffer: Option<VertexBuffer<Vertex>>,
     pub glyph_bg_index_buffer: Option<IndexBuffer<u32>>,
+    /// Extent of the background box, whose corners and border are cut out
+    /// in the fragment shader.
+    pub glyph_bg_box: Option<Quad>,
+    pub shadow_vertex_buffer: Option<VertexBuffer<Vertex>>,
+    pub shadow_index_buffer: Option<IndexBuffer<u32>>,
+    pub bg_shadow_vertex_buffer: Option<VertexBuffer<Vertex>>,
+    pub bg_shadow_index_buffer: Option<IndexBuffer<u32>>,
+    pub stroke_vertex_buffer: Option<VertexBuffer<Vertex>>,
+    pub stroke_index_buffer: Option<IndexBuffer<u32>>,
+    /// Blurred copy of the text the word sharpens from or fades into.
+    pub blur_vertex_buffer: Option<VertexBuffer<Vertex>>,
+    pub blur_index_buffer: Option<IndexBuffer<u32>>,
+    pub decoration_vertex_buffer: Option<VertexBuffer<Vertex>>,
+    pub decoration_index_buffer: Option<IndexBuffer<u32>>,
     pub bg_vertex_buffer: Option<VertexBuffer<Vertex>>,
     pub bg_index_buffer: Option<IndexBuffer<u32>>,
+    pub fg_paint: Option<PaintUniforms>,
+    pub bg_paint: Option<PaintUniforms>,
+    pub canvas_paint: Option<PaintUniforms>,
+    /// Canvas being turned into the current word's, since when and how.
+    pub canvas_transition: Option<(Paint, Instant, Transition)>,
     pub word: Option<Word>,
+    /// Area covered by the text and its box.
+    pub word_bounds: Quad,
+    /// When the animation of each glyph of the layout starts, in seconds.
+    glyph_delays: Vec<f32>,
+    /// Longest a line may be, horizontally and in vertical text.
+    pub max_size: (f32, f32),
+    pub canvas_size: (f32, f32),
 }
 
 impl RenderState {
-    pub fn new(display: &Display) -> Result<Self> {
+    pub fn new(display: &Display, canvas_size: (f32, f32), max_size: (f32, f32)) -> Result<Self> {
         let glyph_program = compile_shaders(display)?;
         let glyph_atlas = GlyphAtlas::new(display, ATLAS_SIZE)?;
         Ok(Self {
@@ -56,9 +145,28 @@ impl RenderState {
             glyph_index_buffer: None,
             glyph_bg_vertex_buffer: None,
             glyph_bg_index_buffer: None,
+            glyph_bg_box: None,
+            shadow_vertex_buffer: None,
+            shadow_index_buffer: None,
+            bg_shadow_vertex_buffer: None,
+            bg_shadow_index_buffer: None,
+            stroke_vertex_buffer: None,
+            stroke_index_buffer: None,
+            blur_vertex_buffer: None,
+            blur_index_buffer: None,
+            decoration_vertex_buffer: None,
+            decoration_index_buffer: None,
             bg_vertex_buffer: None,
             bg_index_buffer: None,
+            fg_paint: None,
+            bg_paint: None,
+            canvas_paint: None,
+            canvas_transition: None,
             word: None,
+            word_bounds: Quad::default(),
+            glyph_delays: Vec::new(),
+            max_size,
+            canvas_size,
         })
     }
 
@@ -67,104 +175,366 @@ impl RenderState {
         display: &Display,
         fontconfig: &FontConfiguration,
     ) -> Result<()> {
-        self.compute_g_vertices(display, fontconfig)?;
+        let word = self.word.as_ref().unwrap();
+        let layout = layout::layout_word(fontconfig, word, self.max_size, self.canvas_size)?;
+        let metrics = fontconfig.get_font(&word.style.font_attributes)?.metrics();
+        let stroke = word.stroke;
+        let shadows: Vec<Shadow> = [word.shadow, word.glow].iter().flatten().copied().collect();
+        let bg_shadows: Vec<Shadow> =
+            [word.bg_shadow, word.bg_glow].iter().flatten().copied().collect();
+        let blur = [word.transition_in, word.transition_out]
+            .iter()
+            .flatten()
+            .filter_map(|transition| transition.blur_radius())
+            .reduce(f32::max);
+        let fg_color = word.style.fg_color.base_color();
+        let stagger = word.glyph_animation.map_or(0., |a| a.stagger.as_secs_f32());
+        self.glyph_delays =
+            layout.cluster_ranks().into_iter().map(|rank| rank as f32 * stagger).collect();
+
+        self.compute_g_vertices(display, &layout)?;
+        match stroke {
+            Some(stroke) => {
+                let em = fontconfig.pixel_size() as f32;
+                let width = PixelLength::new(stroke.width.to_pixels(em) as f64);
+                self.compute_stroke_vertices(display, &layout, stroke, width)?;
+            }
+            None => {
+                self.stroke_vertex_buffer = None;
+                self.stroke_index_buffer = None;
+            }
+        }
+        if shadows.is_empty() {
+            self.shadow_vertex_buffer = None;
+            self.shadow_index_buffer = None;
+        } else {
+            self.compute_shadow_vertices(display, &layout, &shadows)?;
+        }
+        match blur {
+            Some(blur) => self.compute_blur_vertices(display, &layout, blur, fg_color)?,
+            None => {
+                self.blur_vertex_buffer = None;
+                self.blur_index_buffer = None;
+            }
+        }
+        self.compute_decoration_vertices(display, &layout, &metrics)?;
+
+        self.glyph_bg_box = None;
+        self.bg_shadow_vertex_buffer = None;
+        self.bg_shadow_index_buffer = None;
+        if let Some(bg_color) = self.word.as_ref().unwrap().style.bg_color.clone() {
+            let bg_box = self.compute_bg_g_vertices(&bg_color, display, &layout, &metrics)?;
+            if !bg_shadows.is_empty() {
+                self.compute_bg_shadow_vertices(display, bg_box, &bg_shadows)?;
+            }
+        }
 
-        if let Some(bg_color) = self.word.as_ref().unwrap().style.bg_color {
-            self.compute_bg_g_vertices(bg_color, display)?;
+        let ink = self.fg_paint.as_ref().unwrap().rect;
+        let corners = [(ink[0], ink[1]), (ink[2], ink[3])];
+        let box_corners =
+            self.glyph_bg_box.iter().flat_map(|b| [(b.left, b.top), (b.right, b.bottom)]);
+        self.word_bounds = bounds(corners.iter().copied().chain(box_corners)).unwrap_or_default();
+        Ok(())
+    }
+
+    /// How the current word, shown since `start` and for `shown_for`, is
+    /// transformed at `now` by its entrance and exit.
+    pub fn transform(&self, start: Instant, shown_for: Duration, now: Instant) -> Transform {
+        let word = self.word.as_ref().unwrap();
+        let mut transform = Transform::default();
+        if let Some(transition_in) = word.transition_in {
+            let hidden = 1. - transition_in.timing.progress(start, now);
+            transform = transform.combine(transition_in.transform(hidden, true, self.word_bounds));
         }
+        if let Some(transition_out) = word.transition_out {
+            let out_start = (start + shown_for)
+                .checked_sub(transition_out.timing.duration())
+                .unwrap_or(start)
+                .max(start);
+            if now >= out_start {
+                let hidden = transition_out.timing.progress(out_start, now);
+                transform =
+                    transform.combine(transition_out.transform(hidden, false, self.word_bounds));
+            }
+        }
+        transform
+    }
+
+    pub fn compute_g_vertices(&mut self, display: &Display, layout: &Layout) -> Result<()> {
+        let mut verts = Vec::new();
+        let mut indices = Vec::new();
+        let word = self.word.as_ref().unwrap();
+        let fg_paint = word.style.fg_color.clone();
+        let fg_color = color::to_tuple_rgba(fg_paint.base_color());
+
+        for (positioned, &delay) in layout.glyphs.iter().zip(&self.glyph_delays) {
+            let glyph_info = &positioned.info;
+            let font = &positioned.font;
+            let key = glyph_key(positioned, SpriteKind::Fill);
+            if let Some(glyph) = self
+                .glyph_atlas
+                .cached_glyph(key, glyph_info, || Ok(Some(font.rasterize(glyph_info.glyph_pos)?)))?
+            {
+                push_glyph(&mut verts, &mut indices, positioned, &glyph, fg_color, delay);
+            }
+        }
+
+        // Gradients span the inked area of the whole text.
+        let ink = bounds(verts.iter().map(|v| v.position)).unwrap_or_default();
+        self.fg_paint = Some(PaintUniforms::new(display, &fg_paint, ink)?);
+
+        self.glyph_vertex_buffer = Some(VertexBuffer::dynamic(display, &verts)?);
+        self.glyph_index_buffer =
+            Some(IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &indices)?);
         Ok(())
     }
 
-    pub fn compute_g_vertices(
+    /// Stroked outlines of the glyphs, a second sprite per glyph drawn
+    /// under the fill.
+    pub fn compute_stroke_vertices(
         &mut self,
         display: &Display,
-        fontconfig: &FontConfiguration,
+        layout: &Layout,
+        stroke: Stroke,
+        width: PixelLength,
     ) -> Result<()> {
         let mut verts = Vec::new();
         let mut indices = Vec::new();
-        let word = self.word.as_ref().unwrap();
-        let fg_color = color::to_tuple_rgba(word.style.fg_color);
-
-        let font = fontconfig.get_font(&word.style)?;
-        let glyph_infos = font.shape(&word.text)?;
-        let width = glyph_infos.iter().fold(0., |acc, info| acc + info.x_advance.get() as f32);
-        let mut x = -width / 2.;
-        let mut y = 0.;
-        for glyph_info in &glyph_infos {
-            let rasterized_glyph = font.rasterize(glyph_info.glyph_pos)?;
-            let glyph = self.glyph_atlas.load_glyph(rasterized_glyph, glyph_info)?;
-
-            let x0 = x + (glyph.x_offset + glyph.bearing_x).get() as f32;
-            let y0 = y - (glyph.y_offset + glyph.bearing_y).get() as f32;
-
-            let x1 = x0 + glyph.texture.width as f32;
-            let y1 = y0 + glyph.texture.height as f32;
-
-            x += glyph_info.x_advance.get() as f32;
-            y += glyph_info.y_advance.get() as f32;
-            let idx = verts.len() as u32;
-            verts.push(Vertex {
-                position: (x0, y0),
-                tex: (glyph.texture.tex_coords.min_x(), glyph.texture.tex_coords.min_y()),
-                fg_color,
-                ..Default::default()
-            });
-            verts.push(Vertex {
-                position: (x1, y0),
-                tex: (glyph.texture.tex_coords.max_x(), glyph.texture.tex_coords.min_y()),
-                fg_color,
-                ..Default::default()
-            });
-            verts.push(Vertex {
-                position: (x0, y1),
-                tex: (glyph.texture.tex_coords.min_x(), glyph.texture.tex_coords.max_y()),
-                fg_color,
-                ..Default::default()
-            });
-            verts.push(Vertex {
-                position: (x1, y1),
-                tex: (glyph.texture.tex_coords.max_x(), glyph.texture.tex_coords.max_y()),
-                fg_color,
-                ..Default::default()
-            });
-
-            indices.push(idx + V_TOP_LEFT as u32);
-            indices.push(idx + V_TOP_RIGHT as u32);
-            indices.push(idx + V_BOT_LEFT as u32);
-
-            indices.push(idx + V_TOP_RIGHT as u32);
-            indices.push(idx + V_BOT_LEFT as u32);
-            indices.push(idx + V_BOT_RIGHT as u32);
+        let stroke_color = color::to_tuple_rgba(stroke.color);
+
+        for (positioned, &delay) in layout.glyphs.iter().zip(&self.glyph_delays) {
+            let glyph_info = &positioned.info;
+            let font = &positioned.font;
+            let key =
+                glyph_key(positioned, SpriteKind::Stroke { width: (width.get() as f32).to_bits() });
+            if let Some(glyph) = self.glyph_atlas.cached_glyph(key, glyph_info, || {
+                font.rasterize_stroke(glyph_info.glyph_pos, width)
+            })? {
+                push_glyph(&mut verts, &mut indices, positioned, &glyph, stroke_color, delay);
+            }
         }
 
-        self.glyph_vertex_buffer = Some(VertexBuffer::dynamic(display, &verts)?);
-        self.glyph_index_buffer =
+        self.stroke_vertex_buffer = Some(VertexBuffer::dynamic(display, &verts)?);
+        self.stroke_index_buffer =
             Some(IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &indices)?);
         Ok(())
     }
 
-    pub fn compute_bg_g_vertices(
+    /// Blurred copies of the glyphs for the word's shadow and glow,
+    /// drawn under the text.
+    pub fn compute_shadow_vertices(
+        &mut self,
+        display: &Display,
+        layout: &Layout,
+        effects: &[Shadow],
+    ) -> Result<()> {
+        let (verts, indices) = self.blurred_glyph_vertices(layout, effects)?;
+        self.shadow_vertex_buffer = Some(VertexBuffer::dynamic(display, &verts)?);
+        self.shadow_index_buffer =
+            Some(IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &indices)?);
+        Ok(())
+    }
+
+    /// Copy of the glyphs blurred by `blur` pixels, which the word
+    /// sharpens from or fades into. Its sprites are those of a glow of the
+    /// same radius, uploaded once for every word using them.
+    pub fn compute_blur_vertices(
+        &mut self,
+        display: &Display,
+        layout: &Layout,
+        blur: f32,
+        color: RgbaColor,
+    ) -> Result<()> {
+        let blurred = Shadow { offset: (0., 0.), blur, spread: 0., color };
+        let (verts, indices) = self.blurred_glyph_vertices(layout, &[blurred])?;
+        self.blur_vertex_buffer = Some(VertexBuffer::dynamic(display, &verts)?);
+        self.blur_index_buffer =
+            Some(IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &indices)?);
+        Ok(())
+    }
+
+    /// Blurred copies of the glyphs for each effect, grown by its spread
+    /// and moved by its offset.
+    fn blurred_glyph_vertices(
+        &mut self,
+        layout: &Layout,
+        effects: &[Shadow],
+    ) -> Result<(Vec<Vertex>, Vec<u32>)> {
+        let mut verts = Vec::new();
+        let mut indices = Vec::new();
+
+        for effect in effects {
+            let start = verts.len();
+            let color = color::to_tuple_rgba(effect.color);
+            let sigma = shadow::sigma(effect.blur);
+            let kind =
+                SpriteKind::Blurred { spread: effect.spread.to_bits(), sigma: sigma.to_bits() };
+            for (positioned, &delay) in layout.glyphs.iter().zip(&self.glyph_delays) {
+                let glyph_info = &positioned.info;
+                let font = &positioned.font;
+                let key = glyph_key(positioned, kind);
+                let glyph = self.glyph_atlas.cached_glyph(key, glyph_info, || {
+                    let spread = PixelLength::new(effect.spread as f64);
+                    let coverage = if effect.spread > 0. {
+                        font.rasterize_stroke(glyph_info.glyph_pos, spread)?
+                    } else {
+                        None
+                    };
+                    let coverage = match coverage {
+                        Some(coverage) => coverage,
+                        None => font.rasterize(glyph_info.glyph_pos)?,
+                    };
+                    Ok(Some(shadow::blur_glyph(&coverage, sigma)))
+                })?;
+                if let Some(glyph) = glyph {
+                    push_glyph(&mut verts, &mut indices, positioned, &glyph, color, delay);
+                }
+            }
+            for v in verts[start..].iter_mut() {
+                v.position.0 += effect.offset.0;
+                v.position.1 += effect.offset.1;
+            }
+        }
+        Ok((verts, indices))
+    }
+
+    /// Shadow and glow of the `bg_color` box, drawn under it.
+    pub fn compute_bg_shadow_vertices(
         &mut self,
-        bg_color: color::RgbColor,
         display: &Display,
+        bg_box: Quad,
+        effects: &[Shadow],
     ) -> Result<()> {
-        let bg_color = color::to_tuple_rgba(bg_color);
+        let bg_box_style = self.word.as_ref().unwrap().bg_box;
         let mut verts = Vec::new();
         let mut indices = Vec::new();
-        let (mut top, mut left, mut bottom, mut right) = (0f32, 0f32, 0f32, 0f32);
-        let glyph_vertex_buffer = self.glyph_vertex_buffer.as_mut().unwrap();
-        let g_verts = glyph_vertex_buffer.slice_mut(..).unwrap().map_read();
-        for v in g_verts.iter() {
-            left = left.min(v.position.0);
-            right = right.max(v.position.0);
-            top = top.min(v.position.1);
-            bottom = bottom.max(v.position.1);
+
+        for effect in effects {
+            let color = color::to_tuple_rgba(effect.color);
+            let (width, height) = (
+                bg_box.right - bg_box.left + 2. * effect.spread,
+                bg_box.bottom - bg_box.top + 2. * effect.spread,
+            );
+            let radius = bg_box_style.radius + effect.spread;
+            let sigma = shadow::sigma(effect.blur);
+            let key = BoxKey {
+                width: width.to_bits(),
+                height: height.to_bits(),
+                radius: radius.to_bits(),
+                sigma: sigma.to_bits(),
+            };
+            let blurred = self
+                .glyph_atlas
+                .cached_box(key, || shadow::blur_rect(width, height, radius, sigma))?;
+            let sprite = blurred.texture;
+            let left =
+                bg_box.left - effect.spread + effect.offset.0 + blurred.bearing_x.get() as f32;
+            let top = bg_box.top - effect.spread + effect.offset.1 - blurred.bearing_y.get() as f32;
+            let (right, bottom) = (left + sprite.width as f32, top + sprite.height as f32);
+            let corners = [(left, top), (right, top), (left, bottom), (right, bottom)];
+            push_sprite(&mut verts, &mut indices, corners, &sprite, color, 0.);
         }
 
-        left -= PADDING as f32;
-        right += PADDING as f32;
-        top -= PADDING as f32;
-        bottom += PADDING as f32;
+        self.bg_shadow_vertex_buffer = Some(VertexBuffer::dynamic(display, &verts)?);
+        self.bg_shadow_index_buffer =
+            Some(IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &indices)?);
+        Ok(())
+    }
+
+    /// Underline, strikethrough and overline quads for every line, using
+    /// the decoration metrics of the word's font.
+    pub fn compute_decoration_vertices(
+        &mut self,
+        display: &Display,
+        layout: &Layout,
+        metrics: &FontMetrics,
+    ) -> Result<()> {
+        let word = self.word.as_ref().unwrap();
+        let decorations = &word.decorations;
+        let bg_color = color::to_tuple_rgba(
+            decorations.color.unwrap_or_else(|| word.style.fg_color.base_color()),
+        );
+        let underline_thickness = metrics.underline_thickness.get() as f32;
+        let strikeout_thickness = metrics.strikeout_thickness.get() as f32;
+        let mut verts = Vec::new();
+        let mut indices = Vec::new();
+
+        for line in &layout.lines {
+            let lines = [
+                (decorations.underline, metrics.underline_position, underline_thickness),
+                (decorations.strikethrough, metrics.strikeout_position, strikeout_thickness),
+                (decorations.overline, metrics.ascender, underline_thickness),
+            ];
+            for (style, position, thickness) in lines.iter() {
+                if let Some(style) = style {
+                    let center_y = line.baseline - position.get() as f32;
+                    for quad in decoration::decoration_quads(
+                        *style, line.left, line.right, center_y, *thickness,
+                    ) {
+                        let (x0, y0) = layout.to_screen(quad.left, quad.top);
+                        let (x1, y1) = layout.to_screen(quad.right, quad.bottom);
+                        let quad = Quad {
+                            left: x0.min(x1),
+                            top: y0.min(y1),
+                            right: x0.max(x1),
+                            bottom: y0.max(y1),
+                        };
+                        push_quad(&mut verts, &mut indices, quad, bg_color);
+                    }
+                }
+            }
+        }
+
+        self.decoration_vertex_buffer = Some(VertexBuffer::dynamic(display, &verts)?);
+        self.decoration_index_buffer =
+            Some(IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &indices)?);
+        Ok(())
+    }
+
+    /// Background box around the text, padded and grown by the border,
+    /// returned so that effects can follow its shape. Rounded corners and
+    /// the border are drawn by the fragment shader.
+    pub fn compute_bg_g_vertices(
+        &mut self,
+        bg_paint: &Paint,
+        display: &Display,
+        layout: &Layout,
+        metrics: &FontMetrics,
+    ) -> Result<Quad> {
+        let bg_box = self.word.as_ref().unwrap().bg_box;
+        let bg_color = color::to_tuple_rgba(bg_paint.base_color());
+        let mut verts = Vec::new();
+        let mut indices = Vec::new();
+
+        let points: Vec<(f32, f32)> = match bg_box.fit {
+            BoxFit::Ink => {
+                let glyph_vertex_buffer = self.glyph_vertex_buffer.as_mut().unwrap();
+                let g_verts = glyph_vertex_buffer.slice_mut(..).unwrap().map_read();
+                g_verts.iter().map(|v| v.position).collect()
+            }
+            BoxFit::Font => {
+                let (ascender, descender) =
+                    (metrics.ascender.get() as f32, metrics.descender.get() as f32);
+                layout
+                    .lines
+                    .iter()
+                    .flat_map(|line| {
+                        [
+                            layout.to_screen(line.left, line.baseline - ascender),
+                            layout.to_screen(line.right, line.baseline - descender),
+                        ]
+                    })
+                    .collect()
+            }
+        };
+        let Quad { mut left, mut top, mut right, mut bottom } =
+            bounds(points.into_iter()).unwrap_or_default();
+
+        let (padding, border) = (bg_box.padding, bg_box.border_width);
+        left -= padding.left + border;
+        right += padding.right + border;
+        top -= padding.top + border;
+        bottom += padding.bottom + border;
 
         verts.push(Vertex { position: (left, top), bg_color, ..Default::default() });
         verts.push(Vertex { position: (right, top), bg_color, ..Default::default() });
@@ -179,10 +549,13 @@ impl RenderState {
         indices.push(V_BOT_LEFT as u32);
         indices.push(V_BOT_RIGHT as u32);
 
+        let quad = Quad { left, top, right, bottom };
+        self.glyph_bg_box = Some(quad);
+        self.bg_paint = Some(PaintUniforms::new(display, bg_paint, quad)?);
         self.glyph_bg_vertex_buffer = Some(VertexBuffer::dynamic(display, &verts)?);
         self.glyph_bg_index_buffer =
             Some(IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &indices)?);
-        Ok(())
+        Ok(quad)
     }
 
     pub fn compute_bg_vertices(
@@ -191,11 +564,48 @@ impl RenderState {
         window_width: f64,
         window_height: f64,
     ) -> Result<()> {
-        let canvas_color = self.word.as_ref().unwrap().canvas_color;
-        let bg_color = color::to_tuple_rgba(canvas_color);
+        let word = self.word.as_ref().unwrap();
+        let (canvas_paint, transition) = (word.canvas_color.clone(), word.canvas_transition);
         let mut verts = Vec::new();
         let mut indices = Vec::new();
         let (w, h) = (window_width as f32 / 2., window_height as f32 / 2.);
+        let canvas = Quad { left: -w, top: -h, right: w, bottom: h };
+        let now = Instant::now();
+        let changed = self.canvas_paint.as_ref().map(|p| &p.paint) != Some(&canvas_paint);
+        if changed {
+            self.canvas_transition = match (&self.canvas_paint, transition) {
+                (Some(previous), Some(transition)) => {
+                    // Start from what is on screen, even halfway through
+                    // the previous transition.
+                    let shown = match &self.canvas_transition {
+                        Some((from, start, previous_transition)) => {
+                            from.mix(&previous.paint, previous_transition.progress(*start, now))
+                        }
+                        None => previous.paint.clone(),
+                    };
+                    Some((shown, now, transition))
+                }
+                _ => None,
+            };
+        }
+        let (from, amount) = match &self.canvas_transition {
+            Some((from, start, transition)) => (from.clone(), transition.progress(*start, now)),
+            None => (canvas_paint.clone(), 1.),
+        };
+        // The canvas is redrawn every frame, only bake its ramp when the
+        // paint changes or while it is turning into the next one.
+        if changed || self.canvas_transition.is_some() {
+            self.canvas_paint =
+                Some(PaintUniforms::blend(display, &from, &canvas_paint, amount, canvas)?);
+        }
+        if amount >= 1. {
+            self.canvas_transition = None;
+        }
+        let bg_color = color::mix_oklab(
+            color::to_tuple_rgba(from.base_color()),
+            color::to_tuple_rgba(canvas_paint.base_color()),
+            amount,
+        );
 
         verts.push(Vertex { position: (-w, -h), bg_color, ..Default::default() });
         verts.push(Vertex { position: (w, -h), bg_color, ..Default::default() });
@@ -218,6 +628,121 @@ impl RenderState {
     }
 }
 
+/// Smallest quad containing every point, `None` without points.
+fn bounds(points: impl Iterator<Item = (f32, f32)>) -> Option<Quad> {
+    points.fold(None, |quad: Option<Quad>, (x, y)| {
+        Some(match quad {
+            Some(q) => Quad {
+                left: q.left.min(x),
+                top: q.top.min(y),
+                right: q.right.max(x),
+                bottom: q.bottom.max(y),
+            },
+            None => Quad { left: x, top: y, right: x, bottom: y },
+        })
+    })
+}
+
+fn glyph_key(positioned: &PositionedGlyph, kind: SpriteKind) -> GlyphKey {
+    GlyphKey {
+        font: Rc::as_ptr(&positioned.font) as usize,
+        glyph_pos: positioned.info.glyph_pos,
+        kind,
+    }
+}
+
+/// Pushes the sprite of a glyph at its pen position, turned sideways for
+/// rotated glyphs, its animation starting after `delay` seconds.
+fn push_glyph(
+    verts: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    positioned: &PositionedGlyph,
+    glyph: &GlyphTexture<SrgbTexture2d>,
+    fg_color: (f32, f32, f32, f32),
+    delay: f32,
+) {
+    let (x, y) = (positioned.x, positioned.y);
+    // Glyph box relative to the pen position, along and across the
+    // glyph's own baseline.
+    let u0 = (glyph.x_offset + glyph.bearing_x).get() as f32;
+    let v0 = -(glyph.y_offset + glyph.bearing_y).get() as f32;
+    let u1 = u0 + glyph.texture.width as f32;
+    let v1 = v0 + glyph.texture.height as f32;
+    let corner = |u: f32, v: f32| {
+        if positioned.rotated {
+            (x - v, y + u)
+        } else {
+            (x + u, y + v)
+        }
+    };
+    let corners = [corner(u0, v0), corner(u1, v0), corner(u0, v1), corner(u1, v1)];
+    let has_color = if glyph.has_color { 1. } else { 0. };
+    let start = verts.len();
+    push_sprite(verts, indices, corners, &glyph.texture, fg_color, has_color);
+    for v in verts[start..].iter_mut() {
+        v.delay = delay;
+    }
+}
+
+/// Pushes a textured quad, corners in top-left, top-right, bottom-left,
+/// bottom-right order of the sprite.
+fn push_sprite(
+    verts: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    corners: [(f32, f32); 4],
+    sprite: &Sprite<SrgbTexture2d>,
+    fg_color: (f32, f32, f32, f32),
+    has_color: f32,
+) {
+    let tex_coords = &sprite.tex_coords;
+    let tex = [
+        (tex_coords.min_x(), tex_coords.min_y()),
+        (tex_coords.max_x(), tex_coords.min_y()),
+        (tex_coords.min_x(), tex_coords.max_y()),
+        (tex_coords.max_x(), tex_coords.max_y()),
+    ];
+
+    let idx = verts.len() as u32;
+    for (position, tex) in corners.iter().zip(tex.iter()) {
+        verts.push(Vertex {
+            position: *position,
+            tex: *tex,
+            fg_color,
+            has_color,
+            ..Default::default()
+        });
+    }
+
+    indices.push(idx + V_TOP_LEFT as u32);
+    indices.push(idx + V_TOP_RIGHT as u32);
+    indices.push(idx + V_BOT_LEFT as u32);
+
+    indices.push(idx + V_TOP_RIGHT as u32);
+    indices.push(idx + V_BOT_LEFT as u32);
+    indices.push(idx + V_BOT_RIGHT as u32);
+}
+
+fn push_quad(
+    verts: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    quad: Quad,
+    bg_color: (f32, f32, f32, f32),
+) {
+    let idx = verts.len() as u32;
+    verts.push(Vertex { position: (quad.left, quad.top), bg_color, ..Default::default() });
+    verts.push(Vertex { position: (quad.right, quad.top), bg_color, ..Default::default() });
+    verts.push(Vertex { position: (quad.left, quad.bottom), bg_color, ..Default::default() });
+    verts.push(Vertex { position: (quad.right, quad.bottom), bg_color, ..Default::default() });
+
+    indices.push(idx + V_TOP_LEFT as u32);
+    indices.push(idx + V_TOP_RIGHT as u32);
+    indices.push(idx + V_BOT_LEFT as u32);
+
+    indices.push(idx + V_TOP_RIGHT as u32);
+    indices.push(idx + V_BOT_LEFT as u32);
+    indices.push(idx + V_BOT_RIGHT as u32);
+}
+
 fn compile_shaders(display: &Display) -> Result<glium::Program> {
     let glyph_source = glium::program::ProgramCreationInput::SourceCode {
         vertex_shader: GLYPH_VERTEX_SHADER,
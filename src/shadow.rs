@@ -0,0 +1,159 @@
+use crate::font::RasterizedGlyph;
+use crate::utils::PixelLength;
+
+/// Standard deviation of the Gaussian for a blur radius, as CSS does.
+pub fn sigma(blur_radius: f32) -> f32 {
+    blur_radius.max(0.) / 2.
+}
+
+/// Normalized Gaussian weights covering three standard deviations on
+/// each side, which is where the curve becomes negligible.
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.).ceil() as i32;
+    if radius == 0 {
+        return vec![1.];
+    }
+    let weights: Vec<f32> =
+        (-radius..=radius).map(|i| (-((i * i) as f32) / (2. * sigma * sigma)).exp()).collect();
+    let sum: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / sum).collect()
+}
+
+/// Blurs the coverage of `glyph` into a grayscale bitmap grown by the
+/// kernel radius on every side, keeping it aligned with the glyph.
+pub fn blur_glyph(glyph: &RasterizedGlyph, sigma: f32) -> RasterizedGlyph {
+    let kernel = gaussian_kernel(sigma);
+    let pad = kernel.len() / 2;
+    let (width, height) = (glyph.width + 2 * pad, glyph.height + 2 * pad);
+
+    let mut coverage = vec![0f32; width * height];
+    for y in 0..glyph.height {
+        for x in 0..glyph.width {
+            coverage[(y + pad) * width + x + pad] =
+                glyph.data[(y * glyph.width + x) * 4 + 3] as f32 / 255.;
+        }
+    }
+
+    // The Gaussian is separable: blur rows, then columns.
+    let mut rows = vec![0f32; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            rows[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .filter_map(|(k, w)| {
+                    let src = (x + k).checked_sub(pad).filter(|&src| src < width)?;
+                    Some(coverage[y * width + src] * w)
+                })
+                .sum();
+        }
+    }
+    let mut data = vec![0u8; width * height * 4];
+    for y in 0..height {
+        for x in 0..width {
+            let value: f32 = kernel
+                .iter()
+                .enumerate()
+                .filter_map(|(k, w)| {
+                    let src = (y + k).checked_sub(pad).filter(|&src| src < height)?;
+                    Some(rows[src * width + x] * w)
+                })
+                .sum();
+            let value = (value * 255.).round().min(255.) as u8;
+            data[(y * width + x) * 4..][..4].fill(value);
+        }
+    }
+
+    RasterizedGlyph {
+        data,
+        width,
+        height,
+        left: glyph.left - PixelLength::new(pad as f64),
+        top: glyph.top + PixelLength::new(pad as f64),
+        has_color: false,
+    }
+}
+
+/// Coverage of a `width` by `height` rectangle with rounded corners of
+/// `radius`, blurred with `sigma` and grown by the kernel radius on every
+/// side; the origin is the top-left corner of the rectangle.
+pub fn blur_rect(width: f32, height: f32, radius: f32, sigma: f32) -> RasterizedGlyph {
+    if radius > 0. {
+        return blur_glyph(&rounded_rect(width, height, radius), sigma);
+    }
+    // A blurred rectangle is the product of two blurred edges, so sharp
+    // corners need no convolution.
+    let pad = (sigma * 3.).ceil();
+    let (image_width, image_height) =
+        ((width + 2. * pad).ceil() as usize, (height + 2. * pad).ceil() as usize);
+    let profile = |len: usize, extent: f32| -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let center = i as f32 + 0.5 - pad;
+                if sigma == 0. {
+                    return if (0. ..extent).contains(&center) { 1. } else { 0. };
+                }
+                let scale = std::f32::consts::SQRT_2 * sigma;
+                (erf(center / scale) - erf((center - extent) / scale)) / 2.
+            })
+            .collect()
+    };
+    let (columns, rows) = (profile(image_width, width), profile(image_height, height));
+
+    let mut data = vec![0u8; image_width * image_height * 4];
+    for (y, row) in rows.iter().enumerate() {
+        for (x, column) in columns.iter().enumerate() {
+            let value = (row * column * 255.).round().min(255.) as u8;
+            data[(y * image_width + x) * 4..][..4].fill(value);
+        }
+    }
+
+    RasterizedGlyph {
+        data,
+        width: image_width,
+        height: image_height,
+        left: PixelLength::new(-pad as f64),
+        top: PixelLength::new(pad as f64),
+        has_color: false,
+    }
+}
+
+/// Antialiased coverage of a rounded rectangle, from its signed distance.
+fn rounded_rect(width: f32, height: f32, radius: f32) -> RasterizedGlyph {
+    let (image_width, image_height) = (width.ceil() as usize, height.ceil() as usize);
+    let (half_width, half_height) = (width / 2., height / 2.);
+    let radius = radius.min(half_width).min(half_height);
+    let mut data = vec![0u8; image_width * image_height * 4];
+    for y in 0..image_height {
+        for x in 0..image_width {
+            let qx = (x as f32 + 0.5 - half_width).abs() - half_width + radius;
+            let qy = (y as f32 + 0.5 - half_height).abs() - half_height + radius;
+            let outside = (qx.max(0.).powi(2) + qy.max(0.).powi(2)).sqrt();
+            let distance = outside + qx.max(qy).min(0.) - radius;
+            let value = ((0.5 - distance).clamp(0., 1.) * 255.).round() as u8;
+            data[(y * image_width + x) * 4..][..4].fill(value);
+        }
+    }
+    RasterizedGlyph {
+        data,
+        width: image_width,
+        height: image_height,
+        left: PixelLength::new(0.),
+        top: PixelLength::new(0.),
+        has_color: false,
+    }
+}
+
+/// Abramowitz and Stegun approximation 7.1.26, accurate to 1.5e-7.
+fn erf(x: f32) -> f32 {
+    let t = 1. / (1. + 0.3275911 * x.abs());
+    let poly = t
+        * (0.254_829_6
+            + t * (-0.284_496_74 + t * (1.421_413_7 + t * (-1.453_152_1 + t * 1.061_405_4))));
+    let y = 1. - poly * (-x * x).exp();
+    if x < 0. {
+        -y
+    } else {
+        y
+    }
+}
@@ -0,0 +1,190 @@
+use crate::animation::{Easing, Transform};
+use crate::color::{self, RgbaColor};
+use std::time::{Duration, Instant};
+
+/// Values of the animated properties at a point in time, each eased into
+/// the next keyframe that sets it. Properties a keyframe leaves unset
+/// are interpolated between the keyframes around it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub at: Duration,
+    /// Pace towards the next keyframe.
+    pub easing: Easing,
+    pub translate: Option<(f32, f32)>,
+    pub scale: Option<f32>,
+    /// Clockwise, in degrees.
+    pub rotate: Option<f32>,
+    pub opacity: Option<f32>,
+    pub fg_color: Option<RgbaColor>,
+    pub canvas_color: Option<RgbaColor>,
+}
+
+/// Keyframes of a word or of the scene, in time order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Keyframes(Vec<Keyframe>);
+
+impl Keyframes {
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Self {
+        keyframes.sort_by_key(|k| k.at);
+        Self(keyframes)
+    }
+
+    /// The keyframed values `elapsed` into the animation.
+    fn sample(&self, elapsed: Duration) -> Sample {
+        let lerp = |(from, to, amount): (f32, f32, f32)| from + (to - from) * amount;
+        let mut transform = Transform::default();
+        if let Some((from, to, amount)) = self.segment(elapsed, |k| k.translate) {
+            transform.offset = (lerp((from.0, to.0, amount)), lerp((from.1, to.1, amount)));
+        }
+        if let Some(scale) = self.segment(elapsed, |k| k.scale) {
+            transform.scale = lerp(scale);
+        }
+        if let Some(rotate) = self.segment(elapsed, |k| k.rotate) {
+            transform.rotation = lerp(rotate).to_radians();
+        }
+        if let Some(opacity) = self.segment(elapsed, |k| k.opacity) {
+            transform.opacity = lerp(opacity).clamp(0., 1.);
+        }
+        // Colors are mixed as the canvas transitions are, in OKLab.
+        let mix = |(from, to, amount)| {
+            color::mix_oklab(color::to_tuple_rgba(from), color::to_tuple_rgba(to), amount)
+        };
+        Sample {
+            transform,
+            fg_color: self.segment(elapsed, |k| k.fg_color).map(mix),
+            canvas_color: self.segment(elapsed, |k| k.canvas_color).map(mix),
+        }
+    }
+
+    /// The values of a property around `elapsed` and the eased progress
+    /// from one to the other, `None` if no keyframe sets it. The first
+    /// and last values hold before and after the keyframes.
+    fn segment<T: Copy>(
+        &self,
+        elapsed: Duration,
+        property: impl Fn(&Keyframe) -> Option<T>,
+    ) -> Option<(T, T, f32)> {
+        let keys: Vec<(&Keyframe, T)> =
+            self.0.iter().filter_map(|k| property(k).map(|value| (k, value))).collect();
+        let &(first, first_value) = keys.first()?;
+        if elapsed <= first.at {
+            return Some((first_value, first_value, 1.));
+        }
+        for pair in keys.windows(2) {
+            let ((from, from_value), (to, to_value)) = (pair[0], pair[1]);
+            if elapsed < to.at {
+                let t = (elapsed - from.at).as_secs_f32() / (to.at - from.at).as_secs_f32();
+                return Some((from_value, to_value, from.easing.apply(t)));
+            }
+        }
+        let &(_, last_value) = keys.last()?;
+        Some((last_value, last_value, 1.))
+    }
+}
+
+/// Keyframed values of a frame.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    transform: Transform,
+    fg_color: Option<(f32, f32, f32, f32)>,
+    canvas_color: Option<(f32, f32, f32, f32)>,
+}
+
+/// What the timeline decided for a frame.
+pub struct Tick {
+    /// Word shown, and whether it just replaced the previous one.
+    pub index: usize,
+    pub changed: bool,
+    /// When the word was first shown, and for how long it is.
+    pub word_start: Instant,
+    pub word_duration: Duration,
+    /// Scene and word keyframes combined.
+    pub transform: Transform,
+    /// Colors replacing the text's and the canvas' paint, as sRGB.
+    pub fg_color: Option<(f32, f32, f32, f32)>,
+    pub canvas_color: Option<(f32, f32, f32, f32)>,
+}
+
+/// Owns the clock: which word is shown and how its keyframes and the
+/// scene's animate it. Word keyframes are timed from when the word
+/// appears, scene ones from when the first word does, starting over
+/// with it.
+pub struct Timeline {
+    clock: Clock,
+    start: Option<Instant>,
+    scene: Keyframes,
+    words: Vec<Keyframes>,
+}
+
+impl Timeline {
+    /// `durations` and `words` have an entry per word.
+    pub fn new(durations: Vec<Duration>, scene: Keyframes, words: Vec<Keyframes>) -> Self {
+        Self { clock: Clock::new(durations), start: None, scene, words }
+    }
+
+    pub fn tick(&mut self, now: Instant) -> Tick {
+        let (index, changed) = self.clock.tick(now);
+        if index == 0 && changed {
+            self.start = Some(self.clock.word_start());
+        }
+        let word_start = self.clock.word_start();
+        let scene = self.scene.sample(now.saturating_duration_since(self.start.unwrap_or(now)));
+        let word = self.words[index].sample(now.saturating_duration_since(word_start));
+        Tick {
+            index,
+            changed,
+            word_start,
+            word_duration: self.clock.word_duration(),
+            transform: scene.transform.combine(word.transform),
+            fg_color: word.fg_color.or(scene.fg_color),
+            canvas_color: word.canvas_color.or(scene.canvas_color),
+        }
+    }
+}
+
+/// Which word is shown, from the wall clock: each is shown for its
+/// duration, then the next one, looping.
+struct Clock {
+    durations: Vec<Duration>,
+    current: Option<(usize, Instant)>,
+}
+
+impl Clock {
+    fn new(durations: Vec<Duration>) -> Self {
+        Self { durations, current: None }
+    }
+
+    /// Index of the word to show at `now`, and whether it just changed.
+    fn tick(&mut self, now: Instant) -> (usize, bool) {
+        let (index, start) = match self.current {
+            None => (0, now),
+            Some((index, start))
+                if now.saturating_duration_since(start) >= self.durations[index] =>
+            {
+                // Keep the cadence, unless frames were missed for longer
+                // than the next word.
+                let next_index = (index + 1) % self.durations.len();
+                let next = start + self.durations[index];
+                let start = if now.saturating_duration_since(next) >= self.durations[next_index] {
+                    now
+                } else {
+                    next
+                };
+                (next_index, start)
+            }
+            Some((index, _)) => return (index, false),
+        };
+        self.current = Some((index, start));
+        (index, true)
+    }
+
+    /// When the current word was first shown.
+    fn word_start(&self) -> Instant {
+        self.current.map_or_else(Instant::now, |(_, start)| start)
+    }
+
+    /// How long the current word is shown for.
+    fn word_duration(&self) -> Duration {
+        self.durations[self.current.map_or(0, |(index, _)| index)]
+    }
+}